@@ -0,0 +1,26 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// A formula of about `len` bytes, mixing every kind of token
+fn formula(len: usize) -> String {
+    const TERM: &str = r"x^{2} + \frac{a_i}{3.25} - y \pm 10 = ";
+    let mut formula = TERM.repeat(len.div_ceil(TERM.len()));
+    formula.truncate(len - len % TERM.len());
+    formula.push('z');
+    formula
+}
+
+/// Parse time should grow linearly with the input, so the throughput stays flat from 1 KB to 100 KB
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for kb in [1, 10, 50, 100] {
+        let input = formula(kb * 1024);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{}KB", kb)), &input, |b, input| {
+            b.iter(|| rustex::parse(input).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::{bitmap::Bitmap, element::KElement, error::Error, output::{pdf::PdfOptions, png::PngOptions}, renderer::RusTeX};
+
+/// File format every formula of a batch is written in
+#[derive(Debug, Clone)]
+pub enum BatchFormat {
+    /// The source of each formula is stored in its PNG, whatever `PngOptions::source` says
+    Png(PngOptions),
+    Svg,
+    /// One single page document per formula
+    Pdf(PdfOptions),
+}
+
+/// One formula of a batch and the file it is rendered to
+#[derive(Debug, Clone)]
+pub struct BatchItem {
+    pub source: String,
+    pub output: PathBuf,
+}
+
+impl BatchFormat {
+    /// Usual file extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            BatchFormat::Png(_) => "png",
+            BatchFormat::Svg => "svg",
+            BatchFormat::Pdf(_) => "pdf",
+        }
+    }
+}
+
+impl RusTeX {
+    /// Parses and renders every item to its own file, in parallel on the current rayon thread pool.
+    /// Call it inside `ThreadPool::install` to pick the pool. All items share this renderer's glyph cache.
+    ///
+    /// Results are in the order of `items`. A formula that fails to parse, render or be written
+    /// only fails its own item.
+    pub fn render_batch(&self, items: &[BatchItem], format: &BatchFormat) -> Vec<Result<(), Error>> {
+        items.par_iter()
+            .map(|item| self.render_file(&item.source, &item.output, format))
+            .collect()
+    }
+
+    /// Parses and rasterizes every formula in parallel, keeping the results in the order of `sources`
+    pub fn rasterize_batch<S: AsRef<str> + Sync>(&self, sources: &[S]) -> Vec<Result<Bitmap, Error>> {
+        sources.par_iter()
+            .map(|source| self.rasterize(&KElement::parse(source.as_ref())?))
+            .collect()
+    }
+
+    fn render_file(&self, source: &str, path: &Path, format: &BatchFormat) -> Result<(), Error> {
+        let element = KElement::parse(source)?;
+
+        let data = match format {
+            BatchFormat::Png(options) => {
+                let options = PngOptions { source: Some(source.to_string()), ..options.clone() };
+                self.rasterize(&element)?.to_png(&options)?
+            }
+            BatchFormat::Svg => self.render_svg(&element)?.into_bytes(),
+            BatchFormat::Pdf(options) => self.render_pdf(std::slice::from_ref(&element), options.clone())?,
+        };
+
+        std::fs::write(path, data).map_err(|e| Error::Output(format!("{}: {}", path.display(), e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::TeXSettings;
+
+    #[test]
+    fn a_failing_item_does_not_stop_the_others() {
+        let dir = std::env::temp_dir().join(format!("rustex-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let item = |name: &str, source: String| BatchItem { source, output: dir.join(name) };
+        let items = [
+            item("first.svg", "x^2".to_string()),
+            item("nested.svg", format!("{}x{}", "{".repeat(3000), "}".repeat(3000))),
+            item("unknown.svg", r"\fract{1}{2}".to_string()),
+            item("last.svg", r"\frac{1}{2}".to_string()),
+        ];
+
+        let results = RusTeX::new(TeXSettings::default()).render_batch(&items, &BatchFormat::Svg);
+
+        assert!(results[0].is_ok() && results[3].is_ok());
+        assert!(matches!(&results[1], Err(Error::Parse(error)) if error.code() == "E0009"));
+        assert!(matches!(&results[2], Err(Error::Parse(error)) if error.code() == "E0006"));
+        let written: Vec<bool> = items.iter().map(|item| item.output.exists()).collect();
+        assert_eq!(written, [true, false, false, true]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,51 +1,211 @@
 use icy_sixel::{sixel_string, DiffusionMethod, MethodForLargest, MethodForRep, PixelFormat, Quality};
 
+use crate::error::Error;
+
+/// A straight (non-premultiplied) RGBA color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Parses `#rgb`, `#rrggbb` or `#rrggbbaa` (the leading `#` is optional)
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        let channel = |i: usize, len: usize| {
+            u8::from_str_radix(&digits[i*len..(i+1)*len], 16)
+                .map(|v| if len == 1 { v * 17 } else { v })
+                .map_err(|_| Error::InvalidSettings(format!("Invalid color: '{}'", hex)))
+        };
+
+        if !digits.is_ascii() {
+            return Err(Error::InvalidSettings(format!("Invalid color: '{}'", hex)));
+        }
+
+        match digits.len() {
+            3 => Ok(Self::rgb(channel(0, 1)?, channel(1, 1)?, channel(2, 1)?)),
+            6 => Ok(Self::rgb(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+            8 => Ok(Self::rgba(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?, channel(3, 2)?)),
+            _ => Err(Error::InvalidSettings(format!("Invalid color: '{}'", hex))),
+        }
+    }
+
+    /// Returns this color with its alpha multiplied by `coverage` (0.0 - 1.0)
+    pub(crate) fn with_coverage(self, coverage: f32) -> Self {
+        Self {
+            a: (self.a as f32 * coverage.clamp(0.0, 1.0)).round() as u8,
+            ..self
+        }
+    }
+
+    /// Composites this color over `dst` using the source-over operator
+    pub(crate) fn over(self, dst: Color) -> Color {
+        if self.a == 255 || dst.a == 0 {
+            return self;
+        }
+        if self.a == 0 {
+            return dst;
+        }
+
+        let sa = self.a as f32 / 255.0;
+        let da = dst.a as f32 / 255.0;
+        let out_a = sa + da * (1.0 - sa);
+
+        let channel = |s: u8, d: u8| {
+            ((s as f32 * sa + d as f32 * da * (1.0 - sa)) / out_a).round() as u8
+        };
+
+        Color {
+            r: channel(self.r, dst.r),
+            g: channel(self.g, dst.g),
+            b: channel(self.b, dst.b),
+            a: (out_a * 255.0).round() as u8,
+        }
+    }
+
+    /// Multiplies the channels where both colors are present and composites the rest source-over
+    pub(crate) fn multiply(self, dst: Color) -> Color {
+        if self.a == 0 {
+            return dst;
+        }
+        if dst.a == 0 {
+            return self;
+        }
+
+        let sa = self.a as f32 / 255.0;
+        let da = dst.a as f32 / 255.0;
+        let out_a = sa + da * (1.0 - sa);
+
+        let channel = |s: u8, d: u8| {
+            let (s, d) = (s as f32, d as f32);
+            ((s * sa * (1.0 - da) + d * da * (1.0 - sa) + s * d / 255.0 * sa * da) / out_a).round() as u8
+        };
+
+        Color {
+            r: channel(self.r, dst.r),
+            g: channel(self.g, dst.g),
+            b: channel(self.b, dst.b),
+            a: (out_a * 255.0).round() as u8,
+        }
+    }
+
+    /// Combines this color with `dst` using `mode`
+    pub(crate) fn blend(self, dst: Color, mode: BlendMode) -> Color {
+        match mode {
+            BlendMode::Replace => self,
+            BlendMode::Max => Color {
+                r: self.r.max(dst.r),
+                g: self.g.max(dst.g),
+                b: self.b.max(dst.b),
+                a: self.a.max(dst.a),
+            },
+            BlendMode::SourceOver => self.over(dst),
+            BlendMode::Multiply => self.multiply(dst),
+        }
+    }
+}
+
+/// How drawn pixels are combined with the pixels already in a bitmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The drawn pixel is written as is, including its alpha
+    Replace,
+    /// The larger value of every channel is kept
+    Max,
+    /// Standard alpha compositing of the drawn pixel over the existing one
+    SourceOver,
+    /// Channels are multiplied where both pixels are present, which only ever darkens
+    Multiply,
+}
+
 pub struct Bitmap {
-    pub data: Vec<u8>,
+    pub data: Vec<Color>,
     pub width: usize,
     pub height: usize,
+    /// Blend mode used by `draw_line` and glyphs drawn through `Canvas`
+    pub(crate) blend_mode: BlendMode,
 }
 
 impl Bitmap {
+    /// Creates a fully transparent bitmap
     pub fn new(width: usize, height: usize) -> Self {
+        Self::filled(width, height, Color::TRANSPARENT)
+    }
+
+    pub fn filled(width: usize, height: usize, color: Color) -> Self {
         Self {
-            data: vec![0; width*height],
+            data: vec![color; width*height],
             width,
-            height
+            height,
+            blend_mode: BlendMode::SourceOver,
         }
     }
 
-    pub fn from_data(data: Vec<u8>, width: usize, height: usize) -> Self {
-        assert!(data.len() == width * height);
+    /// Creates a bitmap from 1 byte per pixel coverage data (as produced by fontdue), painted in `color`
+    pub(crate) fn from_coverage(coverage: &[u8], width: usize, height: usize, color: Color) -> Self {
+        assert!(coverage.len() == width * height);
         Self {
-            data,
+            data: coverage.iter().map(|&c| color.with_coverage(c as f32 / 255.0)).collect(),
             width,
-            height
+            height,
+            blend_mode: BlendMode::SourceOver,
         }
     }
 
-    pub fn overlay(&mut self, other: &Bitmap, xoffset: usize, yoffset:usize) {
-        for y in 0..other.height {
-            for x in 0..other.width {
-                self.data[(y+yoffset as usize)*self.width + (x+xoffset as usize)] = other.data[y*other.width + x];
+    /// Composites `other` onto this bitmap with its top left corner at the given offset, which may
+    /// be negative. Only the part of `other` that lands inside this bitmap is drawn.
+    pub(crate) fn overlay(&mut self, other: &Bitmap, xoffset: isize, yoffset: isize, mode: BlendMode) {
+        let visible = |offset: isize, len: usize, bound: usize| {
+            let start = offset.saturating_neg().clamp(0, len as isize) as usize;
+            let end = (bound as isize).saturating_sub(offset).clamp(start as isize, len as isize) as usize;
+            start..end
+        };
+        let (xs, ys) = (visible(xoffset, other.width, self.width), visible(yoffset, other.height, self.height));
+
+        for y in ys {
+            let row = (y as isize + yoffset) as usize * self.width;
+            for x in xs.clone() {
+                let index = row + (x as isize + xoffset) as usize;
+                self.data[index] = other.data[y*other.width + x].blend(self.data[index], mode);
             }
         }
     }
 
-    /// Prints a 1 byte per pixel greyscale bitmap to Sixel format in console
-    pub fn print(&self) {
-        let mut bitmap_rgb888 = vec![0; self.width*self.height*3];
+    /// Composites the whole bitmap over a background color. A transparent background leaves it unchanged.
+    pub fn fill_background(&mut self, background: Color) {
+        if background.a == 0 {
+            return;
+        }
+        for pixel in self.data.iter_mut() {
+            *pixel = pixel.over(background);
+        }
+    }
 
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let index = y*self.width + x;
+    /// Prints the bitmap to Sixel format in console. Transparent pixels are shown over black.
+    pub fn print(&self) -> Result<(), Error> {
+        let mut bitmap_rgb888 = vec![0; self.width*self.height*3];
 
-                let pixel = self.data[index];
+        for (index, pixel) in self.data.iter().enumerate() {
+            let pixel = pixel.over(Color::BLACK);
 
-                bitmap_rgb888[index*3] = pixel;
-                bitmap_rgb888[index*3 + 1] = pixel;
-                bitmap_rgb888[index*3 + 2] = pixel;
-            }
+            bitmap_rgb888[index*3] = pixel.r;
+            bitmap_rgb888[index*3 + 1] = pixel.g;
+            bitmap_rgb888[index*3 + 2] = pixel.b;
         }
         
         let sixel_data = sixel_string(
@@ -57,42 +217,41 @@ impl Bitmap {
             MethodForLargest::Auto,
             MethodForRep::Auto,
             Quality::AUTO,
-        ).unwrap();
+        ).map_err(|e| Error::Output(format!("Sixel encoding failed: {}", e)))?;
 
         println!("{}", sixel_data);
-
+        Ok(())
     }
 }
 
 
 impl Bitmap {
     /// Sets a pixel value with bounds checking
-    fn set_pixel(&mut self, x: usize, y: usize, value: u8) {
+    fn set_pixel(&mut self, x: usize, y: usize, value: Color) {
         if x < self.width && y < self.height {
             self.data[y * self.width + x] = value;
         }
     }
 
     /// Gets a pixel value with bounds checking
-    fn get_pixel(&self, x: usize, y: usize) -> u8 {
+    fn get_pixel(&self, x: usize, y: usize) -> Color {
         if x < self.width && y < self.height {
             self.data[y * self.width + x]
         } else {
-            0
+            Color::TRANSPARENT
         }
     }
 
 
     /// Draws an antialiased line with arbitrary thickness
-    pub fn draw_line(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, thickness: f32, color: u8) {
-        let x0 = x0 as f32;
-        let y0 = y0 as f32;
-        let x1 = x1 as f32;
-        let y1 = y1 as f32;
-
+    pub(crate) fn draw_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, thickness: f32, color: Color) {
         let dx = x1 - x0;
         let dy = y1 - y0;
         let length = (dx * dx + dy * dy).sqrt();
+
+        if self.width == 0 || self.height == 0 || !length.is_finite() {
+            return;
+        }
         
         if length < 0.001 {
             // Handle degenerate case of zero-length line
@@ -107,12 +266,17 @@ impl Bitmap {
         // Half thickness for calculations
         let half_thickness = thickness * 0.5;
 
-        // Calculate bounding box with some padding for antialiasing
+        // Calculate bounding box with some padding for antialiasing, clipped to the bitmap
         let padding = (thickness * 0.5 + 1.0).ceil() as i32;
-        let min_x = ((x0.min(x1) - padding as f32).floor() as i32).max(0) as usize;
-        let max_x = ((x0.max(x1) + padding as f32).ceil() as i32).min(self.width as i32 - 1) as usize;
-        let min_y = ((y0.min(y1) - padding as f32).floor() as i32).max(0) as usize;
-        let max_y = ((y0.max(y1) + padding as f32).ceil() as i32).min(self.height as i32 - 1) as usize;
+        let min_x = ((x0.min(x1) - padding as f32).floor() as i32).max(0);
+        let max_x = ((x0.max(x1) + padding as f32).ceil() as i32).min(self.width as i32 - 1);
+        let min_y = ((y0.min(y1) - padding as f32).floor() as i32).max(0);
+        let max_y = ((y0.max(y1) + padding as f32).ceil() as i32).min(self.height as i32 - 1);
+
+        if min_x > max_x || min_y > max_y {
+            return;
+        }
+        let (min_x, max_x, min_y, max_y) = (min_x as usize, max_x as usize, min_y as usize, max_y as usize);
 
         // For each pixel in the bounding box, calculate distance to line
         for y in min_y..=max_y {
@@ -133,11 +297,10 @@ impl Bitmap {
         }
     }
 
-    /// Blends a pixel with the existing value using alpha blending
-    fn blend_pixel(&mut self, x: usize, y: usize, color: u8, alpha: f32) {
+    /// Blends a pixel with the existing value using the bitmap's blend mode
+    fn blend_pixel(&mut self, x: usize, y: usize, color: Color, alpha: f32) {
         if x < self.width && y < self.height {
-            let existing = self.get_pixel(x, y) as f32;
-            let new_value = (existing * (1.0 - alpha) + color as f32 * alpha).round() as u8;
+            let new_value = color.with_coverage(alpha).blend(self.get_pixel(x, y), self.blend_mode);
             self.set_pixel(x, y, new_value);
         }
     }
@@ -158,7 +321,7 @@ impl Bitmap {
 
         // Calculate parameter t for the closest point on the line segment
         let t = ((px - x0) * dx + (py - y0) * dy) / length_sq;
-        let t = t.max(0.0).min(1.0); // Clamp to [0, 1] to stay on segment
+        let t = t.clamp(0.0, 1.0); // Clamp to [0, 1] to stay on segment
 
         // Find the closest point on the line segment
         let closest_x = x0 + t * dx;
@@ -293,4 +456,76 @@ impl Bitmap {
     // fn set_pixel(&mut self, x: usize, y: usize, color: u8) {
     //     self.data[y*self.width + x] = color;
     // }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Overlays an opaque 2x2 child onto a transparent 3x3 bitmap and shows which pixels it covered
+    fn overlaid_at(xoffset: isize, yoffset: isize) -> [&'static str; 3] {
+        let mut bitmap = Bitmap::new(3, 3);
+        bitmap.overlay(&Bitmap::filled(2, 2, Color::WHITE), xoffset, yoffset, BlendMode::SourceOver);
+
+        let row = |y: usize| match (0..3).map(|x| bitmap.data[y*3 + x].a).collect::<Vec<_>>().as_slice() {
+            [0, 0, 0] => "...",
+            [255, 0, 0] => "#..",
+            [255, 255, 0] => "##.",
+            [0, 255, 255] => ".##",
+            [0, 0, 255] => "..#",
+            other => panic!("unexpected row {:?}", other),
+        };
+        [row(0), row(1), row(2)]
+    }
+
+    #[test]
+    fn overlay_clips_negative_offsets() {
+        assert_eq!(overlaid_at(0, 0), ["##.", "##.", "..."]);
+        assert_eq!(overlaid_at(-1, -1), ["#..", "...", "..."]);
+        assert_eq!(overlaid_at(-1, 1), ["...", "#..", "#.."]);
+    }
+
+    #[test]
+    fn overlay_clips_past_the_right_and_bottom_edges() {
+        assert_eq!(overlaid_at(2, 2), ["...", "...", "..#"]);
+        assert_eq!(overlaid_at(1, 2), ["...", "...", ".##"]);
+    }
+
+    #[test]
+    fn overlay_skips_children_entirely_off_canvas() {
+        for (x, y) in [(3, 0), (0, 3), (-2, 0), (0, -2), (isize::MAX, isize::MAX), (isize::MIN, isize::MIN)] {
+            assert_eq!(overlaid_at(x, y), ["...", "...", "..."], "offset ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn replace_keeps_the_source_as_is() {
+        assert_eq!(Color::TRANSPARENT.blend(Color::WHITE, BlendMode::Replace), Color::TRANSPARENT);
+        assert_eq!(Color::rgba(1, 2, 3, 4).blend(Color::BLACK, BlendMode::Replace), Color::rgba(1, 2, 3, 4));
+    }
+
+    #[test]
+    fn max_takes_every_channel_separately() {
+        let blended = Color::rgba(10, 200, 30, 40).blend(Color::rgba(100, 50, 30, 200), BlendMode::Max);
+        assert_eq!(blended, Color::rgba(100, 200, 30, 200));
+    }
+
+    #[test]
+    fn source_over_mixes_by_alpha() {
+        let half_red = Color::rgba(255, 0, 0, 128);
+        assert_eq!(half_red.blend(Color::WHITE, BlendMode::SourceOver), Color::rgb(255, 127, 127));
+        assert_eq!(half_red.blend(Color::TRANSPARENT, BlendMode::SourceOver), half_red);
+        assert_eq!(Color::TRANSPARENT.blend(Color::WHITE, BlendMode::SourceOver), Color::WHITE);
+
+        // Two half covered layers add up to three quarters
+        assert_eq!(half_red.blend(Color::rgba(0, 0, 255, 128), BlendMode::SourceOver), Color::rgba(170, 0, 85, 192));
+    }
+
+    #[test]
+    fn multiply_darkens_where_both_are_present() {
+        let source = Color::rgb(200, 100, 50);
+        assert_eq!(source.blend(Color::rgb(100, 200, 255), BlendMode::Multiply), Color::rgb(78, 78, 50));
+        assert_eq!(source.blend(Color::WHITE, BlendMode::Multiply), source);
+        assert_eq!(source.blend(Color::TRANSPARENT, BlendMode::Multiply), source);
+        assert_eq!(Color::TRANSPARENT.blend(source, BlendMode::Multiply), source);
+    }
+}
@@ -0,0 +1,48 @@
+use fontdue::layout::GlyphPosition;
+
+use crate::{bitmap::{BlendMode, Bitmap, Color}, glyph_cache::GlyphCache};
+
+/// A drawing target for formulas. A whole `LayoutBox` tree is drawn onto one canvas,
+/// at fractional coordinates measured from its top left corner.
+pub(crate) trait Canvas: Sized {
+    fn new(width: usize, height: usize) -> Self;
+
+    fn draw_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, thickness: f32, color: Color);
+
+    /// Draws a glyph positioned by a fontdue layout whose origin is at (`x`, `y`) and whose line
+    /// has its baseline `baseline` below that. Canvases that need pixels take them from `cache`.
+    fn draw_glyph(&mut self, glyph: &GlyphPosition, x: f32, y: f32, baseline: f32, color: Color, cache: &GlyphCache);
+
+    /// Records the Y position of the baseline of the content, for canvases that track one
+    fn set_baseline(&mut self, _baseline: f32) {}
+
+    /// Sets how drawing is combined with what is already on the canvas, for canvases made of pixels
+    fn set_blend_mode(&mut self, _mode: BlendMode) {}
+}
+
+impl Canvas for Bitmap {
+    fn new(width: usize, height: usize) -> Self {
+        Bitmap::new(width, height)
+    }
+
+    fn draw_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, thickness: f32, color: Color) {
+        Bitmap::draw_line(self, x0, y0, x1, y1, thickness, color)
+    }
+
+    fn draw_glyph(&mut self, glyph: &GlyphPosition, x: f32, y: f32, _baseline: f32, color: Color, cache: &GlyphCache) {
+        // Whole pixels are an offset, the fraction left over is rasterized into the glyph
+        let (x, y) = (x + glyph.x, y + glyph.y);
+        let (left, top) = (x.floor(), y.floor());
+        let raster = cache.glyph(glyph.font_index, glyph.key.glyph_index, glyph.key.px, x - left, y - top);
+
+        self.overlay(
+            &Bitmap::from_coverage(&raster.coverage, raster.width, raster.height, color),
+            left as isize,
+            top as isize,
+            self.blend_mode);
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+}
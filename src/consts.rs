@@ -1,11 +1,29 @@
 pub static LINE_WIDTH: f32 = 0.02; // Global scale
 // pub static MIN_LOCAL_SCALE: f32 = 0.1; // Global scale
-pub static TEXT_OFFSET: f32 = 0.; // Local scale
 pub static TEXT_X_PADDING: f32 = 0.0; // Local scale
 pub static OPERATOR_X_PADDING: f32 = 0.1; // Local scale
 
-pub static FRACTION_SCALE: f32 = 0.8; // Local scale
+pub static SCRIPT_SCALE: f32 = 0.7; // global scale, script style
+pub static SCRIPTSCRIPT_SCALE: f32 = 0.5; // global scale, scriptscript style
+
 pub static FRACTION_PADDING: f32 = 0.2; // global scale
+pub static FRACTION_NUMERATOR_SHIFT_DISPLAY: f32 = 0.677; // local scale, minimum baseline to numerator baseline
+pub static FRACTION_NUMERATOR_SHIFT: f32 = 0.394; // local scale, same outside display style
+pub static FRACTION_DENOMINATOR_SHIFT_DISPLAY: f32 = 0.686; // local scale, minimum baseline to denominator baseline
+pub static FRACTION_DENOMINATOR_SHIFT: f32 = 0.345; // local scale, same outside display style
+pub static FRACTION_CLEARANCE_DISPLAY: f32 = 3.; // line widths, minimum gap between the rule and either part
+pub static FRACTION_CLEARANCE: f32 = 1.; // line widths, same outside display style
+
+pub static SUPERSCRIPT_RAISE_DISPLAY: f32 = 0.413; // local scale, inner baseline to superscript baseline
+pub static SUPERSCRIPT_RAISE: f32 = 0.363; // local scale, same in text and script styles
+pub static SUPERSCRIPT_RAISE_CRAMPED: f32 = 0.289; // local scale, same in cramped styles
+pub static SUPERSCRIPT_BOTTOM_MIN: f32 = 0.108; // local scale, lowest the bottom of a superscript may hang
+pub static SUPERSCRIPT_BOTTOM_MAX_WITH_SUBSCRIPT: f32 = 0.345; // local scale, highest the bottom of a superscript is pushed when there is a subscript
+pub static SUPERSCRIPT_DROP: f32 = 0.386; // script scale, how far below the top of a compound base the superscript baseline may go
+
+pub static SUBSCRIPT_SHIFT: f32 = 0.15; // local scale, inner baseline to subscript baseline
+pub static SUBSCRIPT_SHIFT_WITH_SUPERSCRIPT: f32 = 0.247; // local scale, same when there is a superscript too
+pub static SUBSCRIPT_TOP_MAX: f32 = 0.345; // local scale, highest the top of a lone subscript may reach
+pub static SUBSCRIPT_DROP: f32 = 0.05; // script scale, how far below the bottom of a compound base the subscript baseline may go
 
-pub static SUPERSCRIPT_SCALE: f32 = 0.8; // local scale
-pub static SUPERSCRIPT_Y_OFFSET: f32 = 0.4; // local scale
\ No newline at end of file
+pub static SCRIPT_CLEARANCE: f32 = 4.; // line widths, minimum gap between a superscript and a subscript
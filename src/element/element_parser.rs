@@ -1,92 +1,225 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::element::{text_parser::{self, ParsedObject}, KElement};
+use crate::element::{parse_error::{ParseError, Span}, style::MathStyle, text_parser::{self, ParsedObject}, KElement};
+
+/// Collects errors in lenient mode, where failing parts become `KElement::Error` nodes
+struct Recovery<'a> {
+    source: &'a str,
+    errors: Vec<ParseError>,
+}
 
 impl KElement {
-    pub fn parse(input: &str) -> Result<KElement, String> {
+    pub fn parse(input: &str) -> Result<KElement, ParseError> {
         let elems = text_parser::parse(input)?;
         Self::parse_object(&elems)
     }
 
-    pub fn parse_object(elems: &Vec<ParsedObject>) -> Result<KElement, String> {
+    /// Parses as much of the input as possible. Unknown commands, unbalanced braces and other
+    /// problems become `KElement::Error` nodes showing their source, and are returned alongside the tree.
+    pub fn parse_lenient(input: &str) -> (KElement, Vec<ParseError>) {
+        let (elems, errors) = text_parser::parse_lenient(input);
+        let mut recovery = Recovery { source: input, errors };
+        // Errors are always recovered in lenient mode
+        let root = Self::build(&elems, Some(&mut recovery)).unwrap_or_else(|_| KElement::LinearGroup(Vec::new()));
+        // The tokenizer's errors come first, put them all in source order
+        recovery.errors.sort_by_key(|error| error.span.start);
+        (root, recovery.errors)
+    }
+
+    pub(crate) fn parse_object(elems: &[ParsedObject]) -> Result<KElement, ParseError> {
+        Self::build(elems, None)
+    }
+
+    fn build(elems: &[ParsedObject], mut recovery: Option<&mut Recovery>) -> Result<KElement, ParseError> {
 
         let mut root = Vec::new();
+        // Like in TeX, a style switch applies to everything after it in the group, including its own
+        // braces. The next switch overrides it, so it starts a sibling instead of nesting inside.
+        let mut styled: Option<(MathStyle, Vec<KElement>)> = None;
 
         for elem in elems {
+            if let ParsedObject::Func { name, content, super_script, sub_script, .. } = elem
+                && let Some(style) = MathStyle::from_command(name)
+            {
+                if let Some((style, inner)) = styled.take() {
+                    root.push(KElement::Styled { style, inner: Arc::new(KElement::LinearGroup(inner)) });
+                }
+
+                // Like in TeX, scripts right after the switch go on an empty base
+                let mut inner = Vec::new();
+                if !super_script.is_empty() || !sub_script.is_empty() {
+                    let empty = KElement::LinearGroup(Vec::new());
+                    inner.push(Self::with_scripts(empty, super_script, sub_script, recovery.as_deref_mut())?);
+                }
+                for group in content {
+                    if let KElement::LinearGroup(children) = Self::build(group, recovery.as_deref_mut())? {
+                        inner.extend(children);
+                    }
+                }
+                styled = Some((style, inner));
+                continue;
+            }
+
+            let root = styled.as_mut().map_or(&mut root, |(_, inner)| inner);
             match elem {
                 text_parser::ParsedObject::Func { 
                     name, 
                     content, 
                     super_script, 
-                    sub_script 
+                    sub_script,
+                    span
                 } => {
 
-                    if !super_script.is_empty() && !sub_script.is_empty() {
-                        // root.push(KElement::SuperSub { inner: (), upper: (), lower: () }
-                    } else if !super_script.is_empty() {
-                        // root.push(KElement::SuperSub { inner: (), upper: (), lower: () }
-                    } else if !sub_script.is_empty() {
-                        // root.push(KElement::SuperSub { inner: (), upper: (), lower: () }
-                    } else {
-                        
-                        root.push(Self::from_function(name, content)?);
-                    }
+                    let args = content.iter()
+                        .map(|arg| Self::build(arg, recovery.as_deref_mut()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let element = match Self::from_function(name, args, *span) {
+                        Ok(function) => Self::with_scripts(function, super_script, sub_script, recovery.as_deref_mut())?,
+                        // The span includes the scripts, so the error node already shows them
+                        error => Self::recover(error, *span, recovery.as_deref_mut())?,
+                    };
+                    root.push(element);
                 },
                 text_parser::ParsedObject::Var { 
                     text, 
                     super_script, 
-                    sub_script 
+                    sub_script,
+                    ..
                 } => {
-                    if !super_script.is_empty() && !sub_script.is_empty() {
-                        root.push(KElement::SuperSub { 
-                            inner: Rc::new(Self::parse_text(text)), 
-                            upper: Some(Rc::new(Self::parse_object(super_script)?)), 
-                            lower: Some(Rc::new(Self::parse_object(sub_script)?)), 
-                        });
-                    } else if !super_script.is_empty() {
-                        root.push(KElement::SuperSub { 
-                            inner: Rc::new(Self::parse_text(text)), 
-                            upper: Some(Rc::new(Self::parse_object(super_script)?)), 
-                            lower: None 
-                        });
-                    } else if !sub_script.is_empty() {
-                        root.push(KElement::SuperSub { 
-                            inner: Rc::new(Self::parse_text(text)), 
-                            upper: None,
-                            lower: Some(Rc::new(Self::parse_object(super_script)?))
-                        });
-                    } else {
-                        root.push(Self::parse_text(text));
-                    }
+                    root.push(Self::with_scripts(Self::parse_text(text), super_script, sub_script, recovery.as_deref_mut())?);
+                },
+                text_parser::ParsedObject::Group {
+                    inner,
+                    super_script,
+                    sub_script,
+                    ..
+                } => {
+                    let group = Self::build(inner, recovery.as_deref_mut())?;
+                    root.push(Self::with_scripts(group, super_script, sub_script, recovery.as_deref_mut())?);
                 },
                 text_parser::ParsedObject::Operator { 
-                    text 
+                    text,
+                    span
                 } => {
-                    root.push(KElement::from_symbol(text)?);
+                    root.push(Self::recover(KElement::from_symbol(text, *span), *span, recovery.as_deref_mut())?);
                 },
-                text_parser::ParsedObject::Parenthesis { 
-                    // inner, 
-                    // parenthesis_type, 
-                    // super_script, 
-                    // sub_script 
+                text_parser::ParsedObject::Parenthesis {
+                    inner,
+                    parenthesis_type,
+                    super_script,
+                    sub_script,
                     ..
                 } => {
+                    let (open, close) = parenthesis_type.delimiters();
+                    let inner = Arc::new(Self::build(inner, recovery.as_deref_mut())?);
+                    root.push(Self::with_scripts(KElement::Delimited { open, close, inner }, super_script, sub_script, recovery.as_deref_mut())?);
+                },
+                text_parser::ParsedObject::Error { 
+                    text,
+                    ..
+                } => {
+                    root.push(KElement::Error(text.clone()));
                 },
             }
         }
 
+        if let Some((style, inner)) = styled {
+            root.push(KElement::Styled { style, inner: Arc::new(KElement::LinearGroup(inner)) });
+        }
 
-        return Ok(KElement::LinearGroup(root));
+        Ok(KElement::LinearGroup(root))
     }
 
-    pub fn parse_text(str: &str) -> KElement {
-        if let Ok(num) = str.parse::<f64>() {
-            KElement::Decimal(num)
-        } else if let Ok(num) = str.parse::<i64>() {
+    /// Attaches the scripts to `inner`, or returns it as is when there are none
+    fn with_scripts(inner: KElement, super_script: &[ParsedObject], sub_script: &[ParsedObject], mut recovery: Option<&mut Recovery>) -> Result<KElement, ParseError> {
+        if super_script.is_empty() && sub_script.is_empty() {
+            return Ok(inner);
+        }
+
+        let mut script = |objects: &[ParsedObject]| -> Result<Option<Arc<KElement>>, ParseError> {
+            if objects.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(Arc::new(Self::build(objects, recovery.as_deref_mut())?)))
+            }
+        };
+
+        Ok(KElement::SuperSub {
+            inner: Arc::new(inner),
+            upper: script(super_script)?,
+            lower: script(sub_script)?,
+        })
+    }
+
+    /// In lenient mode, records a failed result and replaces it with an error node showing the source in `span`
+    fn recover(result: Result<KElement, ParseError>, span: Span, recovery: Option<&mut Recovery>) -> Result<KElement, ParseError> {
+        match (result, recovery) {
+            (Err(error), Some(recovery)) => {
+                let text = recovery.source.get(span.start..span.end).unwrap_or_default().to_string();
+                recovery.errors.push(error);
+                Ok(KElement::Error(text))
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Numbers without a fraction part are integers, so they stay integers when written back out
+    pub(crate) fn parse_text(str: &str) -> KElement {
+        if let Ok(num) = str.parse::<i64>() {
             KElement::Integer(num)
+        } else if let Ok(num) = str.parse::<f64>() {
+            KElement::Decimal(num)
         } else {
             KElement::Text(str.to_string())
         }
     }
     
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::element::KElement;
+
+    #[test]
+    fn subscript_is_parsed_from_the_subscript() {
+        let KElement::LinearGroup(elements) = KElement::parse("x_y").unwrap() else {
+            panic!("expected a group");
+        };
+        let [KElement::SuperSub { upper: None, lower: Some(lower), .. }] = elements.as_slice() else {
+            panic!("expected a single subscript");
+        };
+
+        assert!(matches!(&**lower, KElement::LinearGroup(script) if matches!(script.as_slice(), [KElement::Text(text)] if text == "y")));
+    }
+
+    #[test]
+    fn style_switches_are_siblings() {
+        let KElement::LinearGroup(elements) = KElement::parse(&r"\textstyle a ".repeat(20000)).unwrap() else {
+            panic!("expected a group");
+        };
+
+        assert_eq!(elements.len(), 20000);
+        assert!(elements.iter().all(|element| matches!(element, KElement::Styled { inner, .. } if matches!(&**inner, KElement::LinearGroup(children) if children.len() == 1))));
+    }
+
+    #[test]
+    fn unknown_functions_keep_their_scripts_in_the_error_node() {
+        let (root, errors) = KElement::parse_lenient(r"\fract{a}^2+x");
+
+        assert_eq!(errors.len(), 1);
+        let KElement::LinearGroup(elements) = root else {
+            panic!("expected a group");
+        };
+        assert!(matches!(&elements[0], KElement::Error(source) if source == r"\fract{a}^2"));
+        assert_eq!(elements.len(), 3);
+    }
+
+    #[test]
+    fn lenient_errors_are_in_source_order() {
+        let (root, errors) = KElement::parse_lenient(r"\fract{1}{2}+x+{a+b");
+
+        let codes: Vec<_> = errors.iter().map(|error| error.code()).collect();
+        assert_eq!(codes, ["E0006", "E0004"]);
+        assert_eq!(root.to_latex(), r"\fract{1}{2} + x + {a+b");
+    }
+}
@@ -1,34 +1,42 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::element::{text_parser::ParsedObject, KElement};
+use crate::element::{parse_error::{suggest, ParseError, ParseErrorKind, Span}, KElement};
 
-fn assert_args(n: usize, start: usize, end: usize, err: &str) -> Result<(), String> {
-    if start <= n && n <= end {
-        Ok(())
-    } else {
-        Err(format!("{err}, {n} != [{start}, {end}]"))
-    }
+/// Names of all supported functions, used for suggestions on unknown ones
+pub const FUNCTIONS: &[&str] = &["frac", "pm", "displaystyle", "textstyle", "scriptstyle", "scriptscriptstyle"];
+
+/// Checks that `function` got exactly `N` arguments and returns them
+fn take_args<const N: usize>(function: &str, args: Vec<KElement>, span: Span) -> Result<[KElement; N], ParseError> {
+    <[KElement; N]>::try_from(args).map_err(|args| ParseError::new(
+        ParseErrorKind::WrongArgumentCount { function: function.to_string(), min: N, max: N, found: args.len() },
+        span
+    ))
 }
 
-// fn derive_symbol(symbol_str: &str, args: &Vec<Vec<ParsedObject>>) -> Result<KElement, String> {
+// fn derive_symbol(symbol_str: &str, args: &[Vec<ParsedObject>]) -> Result<KElement, String> {
 //     assert_args(args.len(), 0, 0, "Symbol cannot take in any args!")?;
 
 //     Ok(KElement::Text(symbol_str.to_string()))
 // }
 
 impl KElement {
-    pub fn from_function(name: &str, args: &Vec<Vec<ParsedObject>>) -> Result<KElement, String> {
+    pub(crate) fn from_function(name: &str, args: Vec<KElement>, span: Span) -> Result<KElement, ParseError> {
         match name {
             "frac" => {
-                assert_args(args.len(), 2, 2, "A fraction must have 2 arguments!")?;
+                let [upper, lower] = take_args(name, args, span)?;
 
                 Ok(KElement::Fraction { 
-                    upper: Rc::new(Self::parse_object(&args[0])?), 
-                    lower: Rc::new(Self::parse_object(&args[1])?) 
+                    upper: Arc::new(upper), 
+                    lower: Arc::new(lower) 
                 })
             }
             "pm" => Ok(KElement::PlusMinus),
-            _ =>  Err(format!("Invalid function: \\{}", name))
+            _ => {
+                // Only underline the backslash and name, not the arguments
+                let name_span = Span::new(span.start, span.start + 1 + name.len());
+                let suggestion = suggest(name, FUNCTIONS).map(str::to_string);
+                Err(ParseError::new(ParseErrorKind::UnknownFunction { name: name.to_string(), suggestion }, name_span))
+            }
         }
     }
 }
\ No newline at end of file
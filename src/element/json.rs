@@ -0,0 +1,152 @@
+//! JSON import and export of parsed formulas.
+//!
+//! Every document is an envelope naming its format and schema version around a `root`:
+//!
+//! ```json
+//! { "format": "rustex-element", "version": 1, "root": { "type": "linear_group", "value": [] } }
+//! ```
+//!
+//! # `rustex-element`
+//!
+//! `root` is a single [`KElement`]. Elements are objects with a snake_case `type` and, except
+//! for operators, a `value`:
+//!
+//! | `type`         | `value`                                                       |
+//! |----------------|---------------------------------------------------------------|
+//! | `linear_group` | array of elements laid out left to right                      |
+//! | `integer`      | integer                                                       |
+//! | `decimal`      | number                                                        |
+//! | `text`         | string                                                        |
+//! | `error`        | source text that failed to parse in lenient mode              |
+//! | `fraction`     | `{ "upper": element, "lower": element }`                      |
+//! | `super_sub`    | `{ "inner": element, "upper": element?, "lower": element? }`  |
+//! | `delimited`    | `{ "open": char, "close": char, "inner": element }`           |
+//! | `styled`       | `{ "style": style, "inner": element }`                        |
+//! | `plus`, `minus`, `equals`, `plus_minus` | none                                 |
+//!
+//! Missing or `null` scripts in `super_sub` mean the script is absent. A `style` is one of
+//! `display`, `text`, `script` or `script_script`.
+//!
+//! # `rustex-parsed`
+//!
+//! `root` is an array of parser output objects, before they are turned into elements. Objects
+//! carry their snake_case `type` alongside their fields:
+//!
+//! | `type`        | fields                                                                  |
+//! |---------------|-------------------------------------------------------------------------|
+//! | `func`        | `name` (without the backslash), `content` (array of argument arrays), `super_script`, `sub_script` |
+//! | `var`         | `text`, `super_script`, `sub_script`                                    |
+//! | `operator`    | `text`                                                                  |
+//! | `parenthesis` | `inner`, `parenthesis_type` (`round` or `square`), `super_script`, `sub_script` |
+//! | `group`       | `inner`, the objects between braces, `super_script`, `sub_script`       |
+//! | `error`       | `text`, source that failed to parse in lenient mode                     |
+//!
+//! Scripts are arrays of objects, empty or omitted when absent. Every object may also carry a
+//! `span`, `{ "start": byte, "end": byte }` in the LaTeX source, which defaults to `0..0`.
+//!
+//! # Versions
+//!
+//! Readers reject documents with a newer version than they know. Fields may be added without a
+//! version bump as long as older readers can ignore them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{element::{text_parser::ParsedObject, KElement}, error::Error};
+
+pub const ELEMENT_FORMAT: &str = "rustex-element";
+pub const PARSED_FORMAT: &str = "rustex-parsed";
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Envelope<'a, T: Serialize> {
+    format: &'a str,
+    version: u32,
+    root: T,
+}
+
+#[derive(Deserialize)]
+struct RawEnvelope {
+    format: String,
+    version: u32,
+    root: serde_json::Value,
+}
+
+impl KElement {
+    /// Serializes the element tree as a `rustex-element` JSON document
+    pub fn to_json(&self) -> Result<String, Error> {
+        to_document(ELEMENT_FORMAT, self)
+    }
+
+    /// Parses LaTeX only as far as the parser objects and serializes them as a `rustex-parsed` JSON document
+    pub fn parse_to_json(input: &str) -> Result<String, Error> {
+        let objects = super::text_parser::parse(input)?;
+        to_document(PARSED_FORMAT, &objects)
+    }
+
+    /// Reads a `rustex-element` or `rustex-parsed` JSON document. Parser objects are turned into
+    /// elements the same way as parsed LaTeX.
+    pub fn from_json(json: &str) -> Result<KElement, Error> {
+        let envelope: RawEnvelope = serde_json::from_str(json).map_err(json_error)?;
+
+        if envelope.version > SCHEMA_VERSION {
+            return Err(Error::Json(format!(
+                "Unsupported {} version {}, the newest supported version is {}",
+                envelope.format, envelope.version, SCHEMA_VERSION
+            )));
+        }
+
+        match envelope.format.as_str() {
+            ELEMENT_FORMAT => serde_json::from_value(envelope.root).map_err(json_error),
+            PARSED_FORMAT => {
+                let objects: Vec<ParsedObject> = serde_json::from_value(envelope.root).map_err(json_error)?;
+                Ok(Self::parse_object(&objects)?)
+            }
+            format => Err(Error::Json(format!("Unknown document format: '{}'", format))),
+        }
+    }
+}
+
+fn to_document<T: Serialize>(format: &str, root: T) -> Result<String, Error> {
+    let envelope = Envelope { format, version: SCHEMA_VERSION, root };
+    serde_json::to_string_pretty(&envelope).map_err(json_error)
+}
+
+fn json_error(error: serde_json::Error) -> Error {
+    Error::Json(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elements_round_trip() {
+        for source in [r"\frac{a+1}{2}^2", r"(x_i)\pm 1.5", r"\displaystyle{y}_{n}^{k}=-3"] {
+            let element = KElement::parse(source).unwrap();
+            let json = element.to_json().unwrap();
+            assert_eq!(KElement::from_json(&json).unwrap(), element, "{}", json);
+        }
+    }
+
+    #[test]
+    fn parsed_objects_give_the_same_elements_as_latex() {
+        let source = r"\frac{a}{b}+[x]^2";
+        let json = KElement::parse_to_json(source).unwrap();
+        assert_eq!(KElement::from_json(&json).unwrap(), KElement::parse(source).unwrap());
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let json = format!(r#"{{ "format": "{}", "version": {}, "root": {{ "type": "plus" }} }}"#, ELEMENT_FORMAT, SCHEMA_VERSION + 1);
+        assert!(matches!(KElement::from_json(&json), Err(Error::Json(message)) if message.contains("version 2")));
+
+        let current = json.replace(&format!("\"version\": {}", SCHEMA_VERSION + 1), &format!("\"version\": {}", SCHEMA_VERSION));
+        assert_eq!(KElement::from_json(&current).unwrap(), KElement::Plus);
+    }
+
+    #[test]
+    fn unknown_formats_are_rejected() {
+        let json = r#"{ "format": "rustex-layout", "version": 1, "root": [] }"#;
+        assert!(matches!(KElement::from_json(json), Err(Error::Json(message)) if message.contains("rustex-layout")));
+    }
+}
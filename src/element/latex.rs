@@ -0,0 +1,213 @@
+use std::fmt;
+
+use crate::element::KElement;
+
+impl KElement {
+    /// Serializes the element back to normalized LaTeX source.
+    ///
+    /// Function arguments and scripts are always braced, binary operators are surrounded by
+    /// single spaces and everything else is written without spaces unless the tokens would merge,
+    /// so parsing the output gives back the same tree.
+    pub fn to_latex(&self) -> String {
+        let mut latex = String::new();
+        self.write_latex(&mut latex);
+        latex
+    }
+
+    fn write_latex(&self, out: &mut String) {
+        match self {
+            KElement::LinearGroup(elems) => {
+                for (i, elem) in elems.iter().enumerate() {
+                    // An operator at the start or after another operator is unary, e.g. -x
+                    let binary = i > 0 && elem.is_operator() && !elems[i - 1].is_operator();
+                    if binary {
+                        out.push(' ');
+                        elem.write_latex(out);
+                        out.push(' ');
+                        continue;
+                    }
+
+                    // A nested group came from braces, which also end any style switch inside it
+                    let mut latex = if let KElement::LinearGroup(_) = elem {
+                        elem.to_braced_latex()
+                    } else {
+                        elem.to_latex()
+                    };
+                    if needs_separator(out, &latex) {
+                        latex.insert(0, ' ');
+                    }
+                    out.push_str(&latex);
+                }
+            }
+            KElement::Integer(i) => out.push_str(&i.to_string()),
+            KElement::Decimal(d) => {
+                // Keep the point, or the number would be read back as an integer
+                let decimal = d.to_string();
+                out.push_str(&decimal);
+                if d.is_finite() && !decimal.contains('.') {
+                    out.push_str(".0");
+                }
+            }
+            KElement::Text(text) => out.push_str(text),
+            KElement::Error(source) => out.push_str(source),
+            KElement::Fraction { upper, lower } => {
+                out.push_str("\\frac");
+                upper.write_braced(out);
+                lower.write_braced(out);
+            }
+            KElement::SuperSub { inner, upper, lower } => {
+                // Only a single token can carry scripts without braces
+                if inner.is_token() {
+                    inner.write_latex(out);
+                } else {
+                    inner.write_braced(out);
+                }
+                if let Some(lower) = lower {
+                    out.push('_');
+                    lower.write_braced(out);
+                }
+                if let Some(upper) = upper {
+                    out.push('^');
+                    upper.write_braced(out);
+                }
+            }
+            KElement::Delimited { open, close, inner } => {
+                out.push(*open);
+                inner.write_latex(out);
+                out.push(*close);
+            }
+            KElement::Styled { style, inner } => {
+                out.push('\\');
+                out.push_str(style.command());
+
+                let latex = inner.to_latex();
+                if needs_separator(out, &latex) {
+                    out.push(' ');
+                }
+                out.push_str(&latex);
+            }
+            KElement::Plus => out.push('+'),
+            KElement::Minus => out.push('-'),
+            KElement::Equals => out.push('='),
+            KElement::PlusMinus => out.push_str("\\pm"),
+        }
+    }
+
+    fn to_braced_latex(&self) -> String {
+        let mut latex = String::new();
+        self.write_braced(&mut latex);
+        latex
+    }
+
+    /// Whether the element can carry scripts without braces and read back the same
+    fn is_token(&self) -> bool {
+        match self {
+            KElement::Integer(i) => *i >= 0,
+            KElement::Decimal(d) => d.is_finite() && *d >= 0.,
+            KElement::Text(text) => text.chars().count() == 1,
+            KElement::Fraction { .. } | KElement::Delimited { .. } | KElement::PlusMinus => true,
+            _ => false,
+        }
+    }
+
+    fn write_braced(&self, out: &mut String) {
+        out.push('{');
+        self.write_latex(out);
+        out.push('}');
+    }
+}
+
+impl fmt::Display for KElement {
+    /// Formats the element as canonical LaTeX, see [`KElement::to_latex`]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_latex())
+    }
+}
+
+/// Whether writing `next` right after `written` would merge two tokens, like `\pm` and `x`
+/// into `\pmx` or two numbers into one
+fn needs_separator(written: &str, next: &str) -> bool {
+    let (Some(last), Some(first)) = (written.chars().last(), next.chars().next()) else {
+        return false;
+    };
+
+    let ends_in_command = written.trim_end_matches(|c: char| c.is_ascii_alphabetic()).ends_with('\\')
+        && last.is_ascii_alphabetic();
+    let numbers_touch = (last.is_ascii_digit() || last == '.') && first.is_ascii_digit();
+
+    (ends_in_command && first.is_ascii_alphabetic()) || numbers_touch
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::element::KElement;
+
+    /// Parses `source`, writes it back out and parses that again, which must give the same tree
+    fn round_trip(source: &str) -> String {
+        let parsed = KElement::parse(source).unwrap();
+        let latex = parsed.to_latex();
+        let reparsed = KElement::parse(&latex).unwrap_or_else(|e| panic!("{:?} does not parse: {}", latex, e));
+
+        assert_eq!(parsed, reparsed, "{:?} was written as {:?}", source, latex);
+        assert_eq!(reparsed.to_latex(), latex, "writing {:?} is not stable", source);
+        latex
+    }
+
+    #[test]
+    fn round_trips_keep_the_tree() {
+        for source in [
+            "x",
+            "x^2+1",
+            "a_i^2",
+            "x_{i+1}",
+            "\\frac{1}{2}-\\frac{a+b}{c}",
+            "1.5\\pm 0.25",
+            "2.0=2",
+            "-x+-y",
+            "{a+b}^2",
+            "{a+b}_i^{n}",
+            "{x}^2",
+            "{\\scriptstyle a}+b",
+            "\\displaystyle\\frac{x}{y}",
+            "\\textstyle x\\pm y",
+            "12\\pm 3",
+        ] {
+            round_trip(source);
+        }
+    }
+
+    #[test]
+    fn parentheses_and_scripted_functions_are_kept() {
+        assert_eq!(round_trip("(a+b)^2"), "(a + b)^{2}");
+        assert_eq!(round_trip("[x]_1"), "[x]_{1}");
+        assert_eq!(round_trip("(\\frac{1}{2})"), "(\\frac{1}{2})");
+        assert_eq!(round_trip("\\frac{a}{b}^2"), "\\frac{a}{b}^{2}");
+        assert_eq!(round_trip("\\pm^2"), "\\pm^{2}");
+        assert_eq!(round_trip("\\displaystyle^2 x"), "\\displaystyle{}^{2}x");
+    }
+
+    #[test]
+    fn integers_stay_integers() {
+        assert_eq!(KElement::parse("2").unwrap(), KElement::LinearGroup(vec![KElement::Integer(2)]));
+        assert_eq!(round_trip("2^3"), "2^{3}");
+    }
+
+    #[test]
+    fn decimals_keep_their_point() {
+        assert_eq!(KElement::parse("2.0").unwrap(), KElement::LinearGroup(vec![KElement::Decimal(2.)]));
+        assert_eq!(round_trip("2.0"), "2.0");
+        assert_eq!(round_trip("0.5"), "0.5");
+    }
+
+    #[test]
+    fn compound_bases_are_braced() {
+        assert_eq!(round_trip("{a+b}^2"), "{a + b}^{2}");
+        assert_eq!(round_trip("{x}^2"), "{x}^{2}");
+        assert_eq!(round_trip("x^2"), "x^{2}");
+    }
+
+    #[test]
+    fn groups_keep_style_switches_inside() {
+        assert_eq!(round_trip("{\\scriptstyle a}+b"), "{\\scriptstyle a} + b");
+    }
+}
@@ -0,0 +1,361 @@
+use fontdue::layout::{CoordinateSystem, Layout, LayoutSettings, TextStyle};
+
+use crate::{bitmap::Color, consts::*, element::{style::{LayoutStyle, MathStyle}, symbols::{EQUALS, MINUS, PLUS, PLUS_MINUS}, KElement}, fonts::{AXIS_HEIGHT, FONTS}, layout::{LayoutBox, LayoutContent, LayoutItem}, settings::TeXSettings};
+
+/// Scratch state for laying out one formula. Every layout gets its own, so renders sharing a
+/// `RusTeX` never touch the same fontdue layout.
+pub(crate) struct LayoutContext<'a> {
+    pub settings: &'a TeXSettings,
+    pub layout: Layout,
+}
+
+impl<'a> LayoutContext<'a> {
+    pub fn new(settings: &'a TeXSettings) -> Self {
+        let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
+
+        layout.reset(&LayoutSettings {
+            ..LayoutSettings::default()
+        });
+
+        Self { settings, layout }
+    }
+}
+
+impl KElement {
+    /// Lays out a whole formula, in display style unless inline rendering was requested
+    pub(crate) fn layout_root(&self, settings: &TeXSettings) -> LayoutBox {
+        let style = if settings.display { MathStyle::Display } else { MathStyle::Text };
+        self.layout(&mut LayoutContext::new(settings), LayoutStyle::new(style))
+    }
+
+    /// Measures and positions the element and its children, producing a box tree that any canvas can draw
+    pub(crate) fn layout(&self, globals: &mut LayoutContext<'_>, style: LayoutStyle) -> LayoutBox {
+        let current_scale = globals.settings.scale * style.scale();
+
+        match self {
+            KElement::LinearGroup(elems) => {
+                let children = elems.iter().map(|elem| elem.layout(globals, style)).collect();
+                Self::layout_row(children)
+            }
+            KElement::Delimited { open, close, inner } => {
+                let mut delimiter = |delimiter: &char| {
+                    Self::layout_text(&mut globals.layout, &delimiter.to_string(), 0, current_scale, TEXT_X_PADDING, globals.settings.foreground)
+                };
+                let (open, close) = (delimiter(open), delimiter(close));
+                Self::layout_row(vec![open, inner.layout(globals, style), close])
+            }
+            KElement::Integer(i) => {
+                Self::layout_text(&mut globals.layout, &i.to_string(), 0, current_scale, TEXT_X_PADDING, globals.settings.foreground)
+            },
+            KElement::Decimal(i) => {
+                Self::layout_text(&mut globals.layout, &i.to_string(), 0, current_scale, TEXT_X_PADDING, globals.settings.foreground)
+            },            
+            KElement::Text(str) => {
+                Self::layout_text(&mut globals.layout, str, 1, current_scale, TEXT_X_PADDING, globals.settings.foreground)
+            },
+            KElement::Error(source) => {
+                Self::layout_text(&mut globals.layout, source, 0, current_scale, TEXT_X_PADDING, globals.settings.error_color)
+            },
+            KElement::Fraction{upper,lower} => {
+                let padding = FRACTION_PADDING * current_scale;
+                let upper = upper.layout(globals, style.numerator());
+                let lower = lower.layout(globals, style.denominator());
+                let (ax, bx) = (upper.width, lower.width);
+                let width = ax.max(bx) + padding*2.;
+
+                // TeX's rule 15d: the parts are shifted away from the baseline by at least the font's
+                // shifts, and further if they would come closer than the clearance to the rule
+                let thickness = globals.settings.scale*LINE_WIDTH;
+                let axis = *AXIS_HEIGHT*current_scale;
+                let (numerator_shift, denominator_shift, clearance) = if style.is_display() {
+                    (FRACTION_NUMERATOR_SHIFT_DISPLAY, FRACTION_DENOMINATOR_SHIFT_DISPLAY, FRACTION_CLEARANCE_DISPLAY)
+                } else {
+                    (FRACTION_NUMERATOR_SHIFT, FRACTION_DENOMINATOR_SHIFT, FRACTION_CLEARANCE)
+                };
+                let clearance = clearance*thickness;
+
+                let mut shift_up = numerator_shift*current_scale;
+                let numerator_gap = (shift_up - upper.depth) - (axis + thickness/2.);
+                if numerator_gap < clearance {
+                    shift_up += clearance - numerator_gap;
+                }
+
+                let mut shift_down = denominator_shift*current_scale;
+                let denominator_gap = (axis - thickness/2.) - (lower.height - shift_down);
+                if denominator_gap < clearance {
+                    shift_down += clearance - denominator_gap;
+                }
+
+                let height = shift_up + upper.height;
+                let lower_y = height + shift_down - lower.height;
+                // The rule is centered on the axis
+                let rule_y = height - axis;
+
+                // The narrower part is centered over or under the wider one
+                let (upper_x, lower_x) = if ax > bx {
+                    (padding, padding + (ax - bx)/2.)
+                } else {
+                    (padding + (bx - ax)/2., padding)
+                };
+
+                LayoutBox {
+                    width,
+                    height,
+                    depth: shift_down + lower.depth,
+                    content: LayoutContent::Items(vec![
+                        LayoutItem::Box { x: upper_x, y: 0., node: upper },
+                        LayoutItem::Box { x: lower_x, y: lower_y, node: lower },
+                        LayoutItem::Rule {
+                            x0: 0.,
+                            y0: rule_y,
+                            x1: width,
+                            y1: rule_y,
+                            thickness,
+                            color: globals.settings.foreground,
+                        },
+                    ]),
+                }
+            }
+            KElement::SuperSub{inner, upper, lower} => {
+                // Scripts on a group, fraction or other compound base hang from its edges
+                let inner_is_symbol = !matches!(**inner, KElement::LinearGroup(_) | KElement::Fraction { .. } | KElement::SuperSub { .. } | KElement::Styled { .. });
+                let inner = inner.layout(globals, style);
+                let upper = upper.as_ref().map(|upper| upper.layout(globals, style.superscript()));
+                let lower = lower.as_ref().map(|lower| lower.layout(globals, style.subscript()));
+                let script_scale = globals.settings.scale * style.superscript().scale();
+
+                // TeX's rule 18a: scripts on a compound base start from its top and bottom
+                let (mut shift_up, mut shift_down) = if inner_is_symbol {
+                    (0., 0.)
+                } else {
+                    (inner.height - SUPERSCRIPT_DROP*script_scale, inner.depth + SUBSCRIPT_DROP*script_scale)
+                };
+
+                // Rule 18c: the superscript is raised by at least the font's shift, and further if its
+                // bottom would hang too low
+                if let Some(upper) = &upper {
+                    let raise = if style.cramped {
+                        SUPERSCRIPT_RAISE_CRAMPED
+                    } else if style.is_display() {
+                        SUPERSCRIPT_RAISE_DISPLAY
+                    } else {
+                        SUPERSCRIPT_RAISE
+                    };
+                    shift_up = shift_up.max(raise*current_scale).max(upper.depth + SUPERSCRIPT_BOTTOM_MIN*current_scale);
+                }
+
+                match (&upper, &lower) {
+                    // Rule 18b: a lone subscript is lowered by at least the font's shift, and further
+                    // if its top would reach too high
+                    (None, Some(lower)) => {
+                        shift_down = shift_down.max(SUBSCRIPT_SHIFT*current_scale).max(lower.height - SUBSCRIPT_TOP_MAX*current_scale);
+                    }
+                    // Rules 18d and 18e: with both scripts, the subscript drops until they are a clearance
+                    // apart. The pair then moves up if the superscript's bottom is below its limit.
+                    (Some(upper), Some(lower)) => {
+                        shift_down = shift_down.max(SUBSCRIPT_SHIFT_WITH_SUPERSCRIPT*current_scale);
+
+                        let clearance = SCRIPT_CLEARANCE*globals.settings.scale*LINE_WIDTH;
+                        let gap = (shift_up - upper.depth) - (lower.height - shift_down);
+                        if gap < clearance {
+                            shift_down += clearance - gap;
+
+                            let sink = SUPERSCRIPT_BOTTOM_MAX_WITH_SUBSCRIPT*current_scale - (shift_up - upper.depth);
+                            if sink > 0. {
+                                shift_up += sink;
+                                shift_down -= sink;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+
+                let (mut height, mut depth, mut scripts_width) = (inner.height, inner.depth, 0f32);
+                if let Some(upper) = &upper {
+                    height = height.max(shift_up + upper.height);
+                    depth = depth.max(upper.depth - shift_up);
+                    scripts_width = upper.width;
+                }
+                if let Some(lower) = &lower {
+                    height = height.max(lower.height - shift_down);
+                    depth = depth.max(shift_down + lower.depth);
+                    scripts_width = scripts_width.max(lower.width);
+                }
+
+                // Both scripts start right after the base
+                let inner_width = inner.width;
+
+                let mut items = vec![LayoutItem::Box { x: 0., y: height - inner.height, node: inner }];
+                if let Some(upper) = upper {
+                    items.push(LayoutItem::Box { x: inner_width, y: height - shift_up - upper.height, node: upper });
+                }
+                if let Some(lower) = lower {
+                    items.push(LayoutItem::Box { x: inner_width, y: height + shift_down - lower.height, node: lower });
+                }
+
+                LayoutBox {
+                    width: inner_width + scripts_width,
+                    height,
+                    depth,
+                    content: LayoutContent::Items(items),
+                }
+            }
+            // Explicit style switches are never cramped
+            KElement::Styled { style, inner } => inner.layout(globals, LayoutStyle::new(*style)),
+            KElement::Plus => Self::layout_symbol(globals, PLUS, current_scale),
+            KElement::Minus => Self::layout_symbol(globals, MINUS, current_scale),
+            KElement::Equals => Self::layout_symbol(globals, EQUALS, current_scale),
+            KElement::PlusMinus => Self::layout_symbol(globals, PLUS_MINUS, current_scale),
+        }
+    }
+
+    /// Places boxes left to right with their baselines on the row's baseline
+    fn layout_row(children: Vec<LayoutBox>) -> LayoutBox {
+        let (mut totalx, mut maxheight, mut maxdepth): (f32, f32, f32) = (0.,0.,0.);
+        let mut positioned = Vec::new();
+
+        for child in children {
+            maxheight = maxheight.max(child.height);
+            maxdepth = maxdepth.max(child.depth);
+
+            let x = totalx;
+            totalx += child.width;
+            positioned.push((x, child));
+        }
+
+        let items = positioned.into_iter()
+            .map(|(x, node)| LayoutItem::Box { x, y: maxheight - node.height, node })
+            .collect();
+
+        LayoutBox {
+            width: totalx,
+            height: maxheight,
+            depth: maxdepth,
+            content: LayoutContent::Items(items),
+        }
+    }
+
+    /// Lays out a run of text as a single box. The height is the ascent of the line, so every run
+    /// at the same size lines up, and the depth is how far the ink reaches below the baseline.
+    ///
+    /// Unlike rasterized glyphs, text is laid out again on every render. It takes a fraction of a
+    /// percent of the render time, so a cache wouldn't pay for itself.
+    pub(crate) fn layout_text(layout: &mut Layout, text: &str, font_index: usize, scale: f32, x_padding: f32, color: Color) -> LayoutBox {
+        layout.clear();
+        layout.append(&FONTS, &TextStyle::new(text, scale, font_index));
+
+        let (mut width, mut bottom): (f32, f32) = (0.,0.);
+        for glyph in layout.glyphs() {
+            width = width.max(glyph.x + glyph.width as f32);
+            bottom = bottom.max(glyph.y + glyph.height as f32);
+        }
+
+        let line_baseline = layout.lines().and_then(|lines| lines.first()).map_or(0., |line| line.baseline_y);
+
+        LayoutBox {
+            width: width + 2.*scale*x_padding,
+            height: line_baseline,
+            depth: (bottom - line_baseline).max(0.),
+            content: LayoutContent::Glyphs {
+                glyphs: layout.glyphs().clone(),
+                line_baseline,
+                xoffset: scale*x_padding,
+                color,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALE: f32 = 40.;
+
+    /// Lays out the only element of `source` in text style
+    fn layout(source: &str) -> LayoutBox {
+        let settings = TeXSettings::builder().scale(SCALE).build().unwrap();
+        let KElement::LinearGroup(elements) = KElement::parse(source).unwrap() else {
+            panic!("expected a group");
+        };
+        let [element] = elements.as_slice() else {
+            panic!("expected a single element");
+        };
+        element.layout(&mut LayoutContext::new(&settings), LayoutStyle::new(MathStyle::Text))
+    }
+
+    /// The children of a box, with how far their baselines are below its own
+    fn children(layout: &LayoutBox) -> Vec<(f32, f32, &LayoutBox)> {
+        let LayoutContent::Items(items) = &layout.content else {
+            panic!("expected items");
+        };
+        items.iter()
+            .map(|item| match item {
+                LayoutItem::Box { x, y, node } => (*x, y + node.height - layout.height, node),
+                LayoutItem::Rule { .. } => panic!("expected boxes only"),
+            })
+            .collect()
+    }
+
+    fn assert_close(left: f32, right: f32) {
+        assert!((left - right).abs() < 0.01, "{} is not {}", left, right);
+    }
+
+    #[test]
+    fn subscript_is_lowered_by_its_shift() {
+        let layout = layout("x_1");
+        let [(0., 0., base), (x, shift, script)] = children(&layout)[..] else {
+            panic!("expected a base and a script");
+        };
+
+        assert_eq!(x, base.width);
+        assert!(shift >= SUBSCRIPT_SHIFT*SCALE);
+        assert!(script.height - shift <= SUBSCRIPT_TOP_MAX*SCALE + 0.01);
+        assert_eq!(layout.height, base.height);
+        assert_close(layout.depth, shift + script.depth);
+        assert_eq!(layout.width, base.width + script.width);
+    }
+
+    #[test]
+    fn both_scripts_are_apart_by_the_clearance() {
+        let both = layout("x_1^2");
+        let [(0., 0., _), (upper_x, upper_shift, upper), (lower_x, lower_shift, lower)] = children(&both)[..] else {
+            panic!("expected a base and two scripts");
+        };
+        let [_, (_, lone_upper_shift, _)] = children(&layout("x^2"))[..] else {
+            panic!("expected a base and a script");
+        };
+        let [_, (_, lone_lower_shift, _)] = children(&layout("x_1"))[..] else {
+            panic!("expected a base and a script");
+        };
+
+        assert_eq!(upper_x, lower_x);
+        assert!(upper_shift <= lone_upper_shift);
+        assert!(lower_shift >= lone_lower_shift.max(SUBSCRIPT_SHIFT_WITH_SUPERSCRIPT*SCALE));
+
+        // Distance from the bottom of the superscript to the top of the subscript
+        let gap = (lower_shift - lower.height) - (upper_shift + upper.depth);
+        assert!(gap >= SCRIPT_CLEARANCE*SCALE*LINE_WIDTH - 0.01);
+        assert_close(both.depth, lower_shift + lower.depth);
+        assert_eq!(both.width, children(&both)[0].2.width + upper.width.max(lower.width));
+    }
+
+    #[test]
+    fn subscript_of_a_group_hangs_below_it() {
+        let layout = layout("{a+b}_i");
+        let [(0., 0., base), (x, shift, _)] = children(&layout)[..] else {
+            panic!("expected a base and a script");
+        };
+
+        assert!(matches!(base.content, LayoutContent::Items(_)));
+        assert_eq!(x, base.width);
+        assert!(shift >= base.depth + SUBSCRIPT_DROP*SCALE*SCRIPT_SCALE);
+        assert!(layout.depth > base.depth);
+    }
+
+    #[test]
+    fn every_kind_of_element_lays_out() {
+        let layout = layout(r"{\scriptstyle x_{y_{z^2}}^{\frac{1}{2}} \pm 2.5 = -{}_1^{} + a_{\frac{b}{c}}}");
+        assert!(layout.width > 0. && layout.height > 0. && layout.depth > 0.);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt::Write;
+
+use crate::element::{style::MathStyle, KElement};
+
+const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+
+impl KElement {
+    /// Serializes the element as a self-contained presentation MathML `<math>` element,
+    /// displayed as a block when `block` is set and inline with the surrounding text otherwise
+    pub fn to_mathml(&self, block: bool) -> String {
+        let mut mathml = format!(
+            r#"<math xmlns="{}" display="{}">"#,
+            MATHML_NAMESPACE, if block { "block" } else { "inline" }
+        );
+        self.write_mathml(&mut mathml, false);
+        mathml.push_str("</math>");
+        mathml
+    }
+
+    /// `prefix` marks an operator that applies to what follows it, like the minus in `-x`
+    fn write_mathml(&self, out: &mut String, prefix: bool) {
+        match self {
+            KElement::LinearGroup(elems) => {
+                // A single child needs no grouping, except that scripts and fractions need exactly one argument
+                if elems.len() != 1 {
+                    out.push_str("<mrow>");
+                }
+                for (i, elem) in elems.iter().enumerate() {
+                    let prefix = i == 0 || elems[i - 1].is_operator();
+                    elem.write_mathml(out, prefix);
+                }
+                if elems.len() != 1 {
+                    out.push_str("</mrow>");
+                }
+            }
+            KElement::Integer(i) => {
+                let _ = write!(out, "<mn>{}</mn>", i);
+            }
+            KElement::Decimal(d) => {
+                let _ = write!(out, "<mn>{}</mn>", d);
+            }
+            KElement::Text(text) => {
+                // Single letters are italic by default, longer identifiers would be upright unlike the rendered output
+                if text.chars().count() == 1 {
+                    let _ = write!(out, "<mi>{}</mi>", escape_xml(text));
+                } else {
+                    let _ = write!(out, r#"<mi mathvariant="italic">{}</mi>"#, escape_xml(text));
+                }
+            }
+            KElement::Error(source) => {
+                let _ = write!(out, "<merror><mtext>{}</mtext></merror>", escape_xml(source));
+            }
+            KElement::Fraction { upper, lower } => {
+                out.push_str("<mfrac>");
+                upper.write_mathml(out, false);
+                lower.write_mathml(out, false);
+                out.push_str("</mfrac>");
+            }
+            KElement::SuperSub { inner, upper, lower } => {
+                let tag = match (upper, lower) {
+                    (Some(_), Some(_)) => "msubsup",
+                    (Some(_), None) => "msup",
+                    (None, Some(_)) => "msub",
+                    (None, None) => {
+                        inner.write_mathml(out, prefix);
+                        return;
+                    }
+                };
+
+                let _ = write!(out, "<{}>", tag);
+                inner.write_mathml(out, false);
+                // msubsup takes the subscript before the superscript
+                for script in [lower, upper].into_iter().flatten() {
+                    script.write_mathml(out, false);
+                }
+                let _ = write!(out, "</{}>", tag);
+            }
+            KElement::Delimited { open, close, inner } => {
+                let _ = write!(out, "<mrow><mo>{}</mo>", escape_xml(&open.to_string()));
+                inner.write_mathml(out, false);
+                let _ = write!(out, "<mo>{}</mo></mrow>", escape_xml(&close.to_string()));
+            }
+            KElement::Styled { style, inner } => {
+                let (display, script_level) = match style {
+                    MathStyle::Display => (true, 0),
+                    MathStyle::Text => (false, 0),
+                    MathStyle::Script => (false, 1),
+                    MathStyle::ScriptScript => (false, 2),
+                };
+                let _ = write!(out, r#"<mstyle displaystyle="{}" scriptlevel="{}">"#, display, script_level);
+                inner.write_mathml(out, prefix);
+                out.push_str("</mstyle>");
+            }
+            KElement::Plus | KElement::Minus | KElement::Equals | KElement::PlusMinus => {
+                let symbol = match self {
+                    KElement::Plus => "+",
+                    KElement::Minus => "−",
+                    KElement::Equals => "=",
+                    _ => "±",
+                };
+                if prefix && !matches!(self, KElement::Equals) {
+                    let _ = write!(out, r#"<mo form="prefix">{}</mo>"#, symbol);
+                } else {
+                    let _ = write!(out, "<mo>{}</mo>", symbol);
+                }
+            }
+        }
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::element::KElement;
+
+    /// MathML inside the `<math>` element
+    fn mathml(source: &str) -> String {
+        let mathml = KElement::parse(source).unwrap().to_mathml(true);
+        let start = mathml.find('>').unwrap() + 1;
+        mathml[start..].strip_suffix("</math>").unwrap().to_string()
+    }
+
+    #[test]
+    fn display_selects_block_or_inline() {
+        let element = KElement::parse("x").unwrap();
+        assert_eq!(element.to_mathml(true), r#"<math xmlns="http://www.w3.org/1998/Math/MathML" display="block"><mi>x</mi></math>"#);
+        assert_eq!(element.to_mathml(false), r#"<math xmlns="http://www.w3.org/1998/Math/MathML" display="inline"><mi>x</mi></math>"#);
+    }
+
+    #[test]
+    fn fractions_become_mfrac() {
+        assert_eq!(mathml(r"\frac{1}{x}"), "<mfrac><mn>1</mn><mi>x</mi></mfrac>");
+        assert_eq!(mathml(r"\frac{a+b}{2}"), "<mfrac><mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow><mn>2</mn></mfrac>");
+    }
+
+    #[test]
+    fn scripts_pick_the_matching_element() {
+        assert_eq!(mathml("a_i^2"), "<msubsup><mi>a</mi><mi>i</mi><mn>2</mn></msubsup>");
+        assert_eq!(mathml("x^2"), "<msup><mi>x</mi><mn>2</mn></msup>");
+        assert_eq!(mathml("x_1"), "<msub><mi>x</mi><mn>1</mn></msub>");
+    }
+
+    #[test]
+    fn operators_and_numbers_are_told_apart() {
+        assert_eq!(mathml("1.5-x"), "<mrow><mn>1.5</mn><mo>−</mo><mi>x</mi></mrow>");
+        assert_eq!(mathml("-x"), r#"<mrow><mo form="prefix">−</mo><mi>x</mi></mrow>"#);
+    }
+}
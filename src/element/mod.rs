@@ -1,25 +1,52 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-mod rasterizer;
+use serde::{Deserialize, Serialize};
+
+mod layout;
 mod text_parser;
 mod element_parser;
 mod functions;
+mod json;
+mod latex;
+mod parse_error;
+mod mathml;
+mod pretty;
+mod style;
 mod symbols;
+mod unicode;
+
+pub use crate::element::{parse_error::{ParseError, ParseErrorKind, Span}, style::MathStyle};
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
 pub enum KElement {
     LinearGroup(Vec<KElement>),
     Integer(i64),
     Decimal(f64),
     Text(String),
     Fraction {
-        upper: Rc<KElement>, 
-        lower: Rc<KElement>
+        upper: Arc<KElement>, 
+        lower: Arc<KElement>
     },
     SuperSub{
-        inner: Rc<KElement>, 
-        upper: Option<Rc<KElement>>,
-        lower: Option<Rc<KElement>>
+        inner: Arc<KElement>, 
+        upper: Option<Arc<KElement>>,
+        lower: Option<Arc<KElement>>
+    },
+    /// Content between a pair of parentheses or brackets
+    Delimited {
+        open: char,
+        close: char,
+        inner: Arc<KElement>,
     },
+    /// Content after a style switch such as `\displaystyle`, up to the end of its group
+    Styled {
+        style: MathStyle,
+        inner: Arc<KElement>,
+    },
+
+    /// Source that failed to parse in lenient mode, shown as is
+    Error(String),
 
     Plus,
     Minus,
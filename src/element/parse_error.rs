@@ -0,0 +1,225 @@
+use std::fmt::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+/// Byte range in the LaTeX source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// A character that starts no token
+    UnexpectedCharacter { found: char },
+    /// A token that can't appear here. `found` and `expected` are descriptions like "`}`".
+    UnexpectedToken { expected: Option<String>, found: String },
+    /// The input ended in the middle of a construct
+    UnexpectedEnd { expected: String },
+    /// An opening brace or parenthesis without its closing counterpart. The span points at the opening one.
+    UnclosedDelimiter { delimiter: char },
+    /// A second `^` or `_` on the same base
+    DoubleScript { script: char },
+    UnknownFunction { name: String, suggestion: Option<String> },
+    WrongArgumentCount { function: String, min: usize, max: usize, found: usize },
+    UnsupportedOperator { operator: String },
+    /// Braces, parentheses or function arguments nested more than `limit` levels deep
+    NestingTooDeep { limit: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// Stable identifier of the error kind, which doesn't change when messages are reworded
+    pub fn code(&self) -> &'static str {
+        match self.kind {
+            ParseErrorKind::UnexpectedCharacter { .. } => "E0001",
+            ParseErrorKind::UnexpectedToken { .. } => "E0002",
+            ParseErrorKind::UnexpectedEnd { .. } => "E0003",
+            ParseErrorKind::UnclosedDelimiter { .. } => "E0004",
+            ParseErrorKind::DoubleScript { .. } => "E0005",
+            ParseErrorKind::UnknownFunction { .. } => "E0006",
+            ParseErrorKind::WrongArgumentCount { .. } => "E0007",
+            ParseErrorKind::UnsupportedOperator { .. } => "E0008",
+            ParseErrorKind::NestingTooDeep { .. } => "E0009",
+        }
+    }
+
+    /// Short note shown next to the underlined source
+    fn label(&self) -> Option<String> {
+        match &self.kind {
+            ParseErrorKind::UnexpectedToken { expected: Some(expected), .. } => Some(format!("expected {}", expected)),
+            ParseErrorKind::UnexpectedEnd { expected } => Some(format!("expected {}", expected)),
+            ParseErrorKind::UnclosedDelimiter { delimiter } => Some(format!("this `{}` is never closed", delimiter)),
+            ParseErrorKind::DoubleScript { script } => Some(format!("wrap one script in braces, e.g. `x{}{{a{}b}}`", script, script)),
+            ParseErrorKind::UnknownFunction { suggestion: Some(suggestion), .. } => Some(format!("did you mean `\\{}`?", suggestion)),
+            _ => None,
+        }
+    }
+
+    /// Renders the error rustc style, with the offending part of `source` underlined:
+    ///
+    /// ```text
+    /// error[E0006]: unknown function `\fract`
+    ///  --> 1:1
+    ///   |
+    /// 1 | \fract{1}{2}
+    ///   | ^^^^^^ did you mean `\frac`?
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let start = source.floor_char_boundary(self.span.start);
+        let end = source.floor_char_boundary(self.span.end).max(start);
+
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+        let line = &source[line_start..line_end];
+        let line_number = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+
+        // Underline at least one column, even for empty spans such as the end of the input
+        let underlined = source[start..end.min(line_end)].chars().count().max(1);
+
+        let gutter = " ".repeat(line_number.to_string().len());
+        let mut output = format!("error[{}]: {}\n", self.code(), self);
+        let _ = writeln!(output, "{}--> {}:{}", gutter, line_number, column);
+        let _ = writeln!(output, "{} |", gutter);
+        let _ = writeln!(output, "{} | {}", line_number, line);
+        let _ = write!(output, "{} | {}{}", gutter, " ".repeat(column - 1), "^".repeat(underlined));
+        if let Some(label) = self.label() {
+            let _ = write!(output, " {}", label);
+        }
+
+        output
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnexpectedCharacter { found } => write!(f, "unexpected character `{}`", found),
+            ParseErrorKind::UnexpectedToken { expected: Some(expected), found } => write!(f, "expected {}, found {}", expected, found),
+            ParseErrorKind::UnexpectedToken { expected: None, found } => write!(f, "unexpected {}", found),
+            ParseErrorKind::UnexpectedEnd { expected } => write!(f, "unexpected end of input, expected {}", expected),
+            ParseErrorKind::UnclosedDelimiter { delimiter } => write!(f, "unclosed `{}`", delimiter),
+            ParseErrorKind::DoubleScript { script: '^' } => write!(f, "double superscript"),
+            ParseErrorKind::DoubleScript { .. } => write!(f, "double subscript"),
+            ParseErrorKind::UnknownFunction { name, .. } => write!(f, "unknown function `\\{}`", name),
+            ParseErrorKind::WrongArgumentCount { function, min, max, found } => {
+                let expected = if min == max { min.to_string() } else { format!("{} to {}", min, max) };
+                let arguments = if *max == 1 { "argument" } else { "arguments" };
+                let given = if *found == 1 { "was" } else { "were" };
+                write!(f, "`\\{}` takes {} {} but {} {} given", function, expected, arguments, found, given)
+            }
+            ParseErrorKind::UnsupportedOperator { operator } => write!(f, "unsupported operator `{}`", operator),
+            ParseErrorKind::NestingTooDeep { limit } => write!(f, "formula nested more than {} levels deep", limit),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Closest candidate within a few edits of `name`, for "did you mean" suggestions
+pub fn suggest<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).clamp(1, 3);
+    candidates.iter()
+        .map(|candidate| (levenshtein(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unknown_function(span: Span) -> ParseError {
+        ParseError::new(ParseErrorKind::UnknownFunction { name: "fract".to_string(), suggestion: Some("frac".to_string()) }, span)
+    }
+
+    #[test]
+    fn render_points_at_the_right_line_and_column() {
+        let source = "x+1\n= \\fract{a}";
+        assert_eq!(unknown_function(Span::new(6, 12)).render(source), [
+            "error[E0006]: unknown function `\\fract`",
+            " --> 2:3",
+            "  |",
+            "2 | = \\fract{a}",
+            "  |   ^^^^^^ did you mean `\\frac`?",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn render_underlines_one_column_at_the_end_of_input() {
+        let error = ParseError::new(ParseErrorKind::UnexpectedEnd { expected: "`}`".to_string() }, Span::new(3, 3));
+        assert_eq!(error.render("{ab"), [
+            "error[E0003]: unexpected end of input, expected `}`",
+            " --> 1:4",
+            "  |",
+            "1 | {ab",
+            "  |    ^ expected `}`",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn render_moves_spans_inside_a_character_to_its_start() {
+        let error = ParseError::new(ParseErrorKind::UnexpectedCharacter { found: 'é' }, Span::new(3, 4));
+        assert_eq!(error.render("a+é+b"), [
+            "error[E0001]: unexpected character `é`",
+            " --> 1:3",
+            "  |",
+            "1 | a+é+b",
+            "  |   ^",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn suggestions_are_close_names_only() {
+        let functions = ["frac", "pm", "displaystyle", "textstyle"];
+        assert_eq!(suggest("fract", &functions), Some("frac"));
+        assert_eq!(suggest("textstyl", &functions), Some("textstyle"));
+        assert_eq!(suggest("sqrt", &functions), None);
+    }
+
+    #[test]
+    fn argument_counts_agree_in_number() {
+        let message = |min, max, found| ParseError::new(
+            ParseErrorKind::WrongArgumentCount { function: "f".to_string(), min, max, found },
+            Span::default(),
+        ).to_string();
+
+        assert_eq!(message(2, 2, 1), "`\\f` takes 2 arguments but 1 was given");
+        assert_eq!(message(1, 1, 0), "`\\f` takes 1 argument but 0 were given");
+        assert_eq!(message(1, 2, 3), "`\\f` takes 1 to 2 arguments but 3 were given");
+    }
+}
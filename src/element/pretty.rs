@@ -0,0 +1,217 @@
+use crate::element::KElement;
+
+/// A rectangular block of characters with every row the same width
+struct TextBox {
+    rows: Vec<Vec<char>>,
+    /// Row that lines up with the baseline of neighbouring boxes
+    baseline: usize,
+}
+
+impl TextBox {
+    fn line(text: &str) -> Self {
+        Self { rows: vec![text.chars().collect()], baseline: 0 }
+    }
+
+    fn width(&self) -> usize {
+        self.rows[0].len()
+    }
+
+    fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Places boxes side by side with their baselines aligned
+    fn beside(boxes: Vec<TextBox>) -> Self {
+        let above = boxes.iter().map(|b| b.baseline).max().unwrap_or(0);
+        let below = boxes.iter().map(|b| b.height() - b.baseline - 1).max().unwrap_or(0);
+
+        let mut rows = vec![Vec::new(); above + below + 1];
+        for b in &boxes {
+            let top = above - b.baseline;
+            for (y, row) in rows.iter_mut().enumerate() {
+                match y.checked_sub(top).and_then(|y| b.rows.get(y)) {
+                    Some(source) => row.extend_from_slice(source),
+                    None => row.extend(std::iter::repeat_n(' ', b.width())),
+                }
+            }
+        }
+
+        Self { rows, baseline: above }
+    }
+
+    /// Centers the box within `width` columns, leaning left when the padding is uneven
+    fn centered(mut self, width: usize) -> Self {
+        let left = (width - self.width()) / 2;
+        let right = width - self.width() - left;
+        for row in &mut self.rows {
+            row.splice(0..0, std::iter::repeat_n(' ', left));
+            row.extend(std::iter::repeat_n(' ', right));
+        }
+        self
+    }
+
+    fn padded_right(mut self, width: usize) -> Self {
+        for row in &mut self.rows {
+            row.resize(width, ' ');
+        }
+        self
+    }
+
+    /// Wraps the box in parentheses that grow to its height
+    fn parenthesized(self, unicode: bool) -> Self {
+        self.delimited('(', ')', unicode)
+    }
+
+    /// Wraps the box in a pair of delimiters. Parentheses and brackets grow to its height, other
+    /// delimiters are repeated on every row.
+    fn delimited(self, open: char, close: char, unicode: bool) -> Self {
+        let height = self.height();
+        // Top, middle and bottom pieces of each side
+        let pieces = match (open, unicode) {
+            _ if height == 1 => [(open, close); 3],
+            ('(', true) => [('⎛', '⎞'), ('⎜', '⎟'), ('⎝', '⎠')],
+            ('(', false) => [('/', '\\'), ('|', '|'), ('\\', '/')],
+            ('[', true) => [('⎡', '⎤'), ('⎢', '⎥'), ('⎣', '⎦')],
+            _ => [(open, close); 3],
+        };
+        let (left, right): (Vec<char>, Vec<char>) = (0..height).map(|y| match y {
+            0 => pieces[0],
+            _ if y == height - 1 => pieces[2],
+            _ => pieces[1],
+        }).unzip();
+
+        let baseline = self.baseline;
+        let rows = self.rows.into_iter()
+            .zip(left.into_iter().zip(right))
+            .map(|(row, (l, r))| std::iter::once(l).chain(row).chain(std::iter::once(r)).collect())
+            .collect();
+        Self { rows, baseline }
+    }
+}
+
+impl KElement {
+    /// Lays the element out on a character grid, with fractions stacked over a bar and scripts
+    /// raised or lowered by a row. Rows are separated by newlines and trailing spaces are trimmed,
+    /// so the output stays aligned in any monospace font.
+    ///
+    /// `unicode` selects box drawing characters and `−`, otherwise only ASCII is used.
+    pub fn to_pretty(&self, unicode: bool) -> String {
+        self.pretty_box(unicode).rows
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn pretty_box(&self, unicode: bool) -> TextBox {
+        match self {
+            KElement::LinearGroup(elems) => {
+                if elems.is_empty() {
+                    return TextBox::line("");
+                }
+
+                let boxes = elems.iter().enumerate().map(|(i, elem)| {
+                    let symbol = elem.pretty_box(unicode);
+                    // An operator at the start or after another operator is unary and stays unspaced
+                    if elem.is_operator() && i > 0 && !elems[i - 1].is_operator() {
+                        TextBox::beside(vec![TextBox::line(" "), symbol, TextBox::line(" ")])
+                    } else {
+                        symbol
+                    }
+                }).collect();
+                TextBox::beside(boxes)
+            }
+            KElement::Integer(i) => TextBox::line(&i.to_string()),
+            KElement::Decimal(d) => TextBox::line(&d.to_string()),
+            KElement::Text(text) => TextBox::line(text),
+            KElement::Error(source) => TextBox::line(source),
+            KElement::Fraction { upper, lower } => {
+                let upper = upper.pretty_box(unicode);
+                let lower = lower.pretty_box(unicode);
+                let width = upper.width().max(lower.width());
+
+                let bar = if unicode { '─' } else { '-' };
+                let baseline = upper.height();
+                let mut rows = upper.centered(width).rows;
+                rows.push(vec![bar; width]);
+                rows.extend(lower.centered(width).rows);
+
+                TextBox { rows, baseline }
+            }
+            KElement::SuperSub { inner, upper, lower } => {
+                let mut inner_box = inner.pretty_box(unicode);
+                if !inner.is_atom() {
+                    inner_box = inner_box.parenthesized(unicode);
+                }
+
+                let upper = upper.as_ref().map(|u| u.pretty_box(unicode));
+                let lower = lower.as_ref().map(|l| l.pretty_box(unicode));
+                let script_width = upper.iter().chain(&lower).map(TextBox::width).max().unwrap_or(0);
+
+                // The superscript sits entirely above the inner box and the subscript entirely below it
+                let mut script_rows = Vec::new();
+                let mut baseline = inner_box.baseline;
+                if let Some(upper) = upper {
+                    baseline += upper.height();
+                    script_rows.extend(upper.padded_right(script_width).rows);
+                }
+                script_rows.extend(std::iter::repeat_n(vec![' '; script_width], inner_box.height()));
+                if let Some(lower) = lower {
+                    script_rows.extend(lower.padded_right(script_width).rows);
+                }
+
+                let inner_top = baseline - inner_box.baseline;
+                let inner_width = inner_box.width();
+                let rows = script_rows.into_iter().enumerate().map(|(y, script_row)| {
+                    let mut row = match y.checked_sub(inner_top).and_then(|y| inner_box.rows.get(y)) {
+                        Some(inner_row) => inner_row.clone(),
+                        None => vec![' '; inner_width],
+                    };
+                    row.extend(script_row);
+                    row
+                }).collect();
+
+                TextBox { rows, baseline }
+            }
+            KElement::Delimited { open, close, inner } => inner.pretty_box(unicode).delimited(*open, *close, unicode),
+            KElement::Styled { inner, .. } => inner.pretty_box(unicode),
+            KElement::Plus => TextBox::line("+"),
+            KElement::Minus => TextBox::line(if unicode { "−" } else { "-" }),
+            KElement::Equals => TextBox::line("="),
+            KElement::PlusMinus => TextBox::line(if unicode { "±" } else { "+/-" }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::element::KElement;
+
+    fn pretty(source: &str, unicode: bool) -> String {
+        KElement::parse(source).unwrap().to_pretty(unicode)
+    }
+
+    #[test]
+    fn fractions_are_stacked_and_centered() {
+        assert_eq!(pretty(r"\frac{a+b}{c}", true), "a + b\n─────\n  c");
+    }
+
+    #[test]
+    fn exponents_are_raised_by_a_row() {
+        assert_eq!(pretty("x^2", true), " 2\nx");
+        assert_eq!(pretty(r"x^{2}+\frac{1}{2}", true), " 2   1\nx  + ─\n     2");
+    }
+
+    #[test]
+    fn nested_fractions_stack_again() {
+        assert_eq!(pretty(r"\frac{\frac{1}{2}}{3}", true), "1\n─\n2\n─\n3");
+    }
+
+    #[test]
+    fn ascii_mode_avoids_box_drawing() {
+        assert_eq!(pretty(r"\frac{a}{b}-x_1", false), "a\n- - x\nb    1");
+        assert_eq!(pretty(r"(\frac{1}{2})^2", false), "   2\n/1\\\n|-|\n\\2/");
+        assert_eq!(pretty(r"(\frac{1}{2})^2", true), "   2\n⎛1⎞\n⎜─⎟\n⎝2⎠");
+        assert!(pretty(r"\frac{1}{x}\pm\frac{a}{2}", false).is_ascii());
+    }
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+use crate::consts::{SCRIPTSCRIPT_SCALE, SCRIPT_SCALE};
+
+/// One of TeX's four math styles, which decide the size of everything laid out in them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MathStyle {
+    Display,
+    Text,
+    Script,
+    ScriptScript,
+}
+
+/// The style a box is laid out in. Cramped styles, used below fraction rules and in subscripts,
+/// raise superscripts less.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutStyle {
+    pub style: MathStyle,
+    pub cramped: bool,
+}
+
+impl MathStyle {
+    /// Names of the commands that switch to each style, without the backslash
+    pub const COMMANDS: &[(&str, MathStyle)] = &[
+        ("displaystyle", MathStyle::Display),
+        ("textstyle", MathStyle::Text),
+        ("scriptstyle", MathStyle::Script),
+        ("scriptscriptstyle", MathStyle::ScriptScript),
+    ];
+
+    pub fn from_command(name: &str) -> Option<MathStyle> {
+        Self::COMMANDS.iter().find(|(command, _)| *command == name).map(|(_, style)| *style)
+    }
+
+    pub fn command(self) -> &'static str {
+        Self::COMMANDS.iter().find(|(_, style)| *style == self).map(|(command, _)| *command).unwrap()
+    }
+
+    /// Style of scripts attached to something in this style
+    fn script(self) -> MathStyle {
+        match self {
+            MathStyle::Display | MathStyle::Text => MathStyle::Script,
+            MathStyle::Script | MathStyle::ScriptScript => MathStyle::ScriptScript,
+        }
+    }
+
+    /// Style of the numerator and denominator of a fraction in this style
+    fn fraction(self) -> MathStyle {
+        match self {
+            MathStyle::Display => MathStyle::Text,
+            MathStyle::Text => MathStyle::Script,
+            MathStyle::Script | MathStyle::ScriptScript => MathStyle::ScriptScript,
+        }
+    }
+}
+
+impl LayoutStyle {
+    pub fn new(style: MathStyle) -> Self {
+        Self { style, cramped: false }
+    }
+
+    pub fn is_display(self) -> bool {
+        self.style == MathStyle::Display
+    }
+
+    /// Font size relative to the base scale
+    pub fn scale(self) -> f32 {
+        match self.style {
+            MathStyle::Display | MathStyle::Text => 1.,
+            MathStyle::Script => SCRIPT_SCALE,
+            MathStyle::ScriptScript => SCRIPTSCRIPT_SCALE,
+        }
+    }
+
+    pub fn superscript(self) -> Self {
+        Self { style: self.style.script(), cramped: self.cramped }
+    }
+
+    /// Subscripts are always cramped, so anything raised inside them stays low
+    pub fn subscript(self) -> Self {
+        Self { style: self.style.script(), cramped: true }
+    }
+
+    pub fn numerator(self) -> Self {
+        Self { style: self.style.fraction(), cramped: self.cramped }
+    }
+
+    pub fn denominator(self) -> Self {
+        Self { style: self.style.fraction(), cramped: true }
+    }
+}
@@ -1,46 +1,39 @@
-use crate::{bitmap::Bitmap, consts::OPERATOR_X_PADDING, element::KElement, RusTeX};
+use crate::{consts::OPERATOR_X_PADDING, element::{layout::LayoutContext, parse_error::{ParseError, ParseErrorKind, Span}, KElement}, fonts::AXIS_HEIGHT, layout::{LayoutBox, LayoutContent}};
 
-pub const PLUS: &'static str = "+";
-pub const MINUS: &'static str = "-";
-pub const EQUALS: &'static str = "=";
-pub const PLUS_MINUS: &'static str = "±";
-
-pub const PLUS_YOFFSET: f32 = 0.15;
-pub const MINUS_YOFFSET: f32 = 0.15;
-pub const EQUALS_YOFFSET: f32 = 0.27;
-pub const PLUSMMINUS_YOFFSET: f32 = 0.15;
+pub const PLUS: &str = "+";
+pub const MINUS: &str = "-";
+pub const EQUALS: &str = "=";
+pub const PLUS_MINUS: &str = "±";
 
 impl KElement {
-    pub fn from_symbol(symbol: &str) -> Result<KElement, String> {
+    pub(crate) fn from_symbol(symbol: &str, span: Span) -> Result<KElement, ParseError> {
         match symbol {
             "+" => Ok(KElement::Plus),
             "-" => Ok(KElement::Minus),
             "=" => Ok(KElement::Equals),
             "±" => Ok(KElement::PlusMinus),
-            _ => unreachable!("Unimplemented symbol: {}", symbol),
+            _ => Err(ParseError::new(ParseErrorKind::UnsupportedOperator { operator: symbol.to_string() }, span)),
             // _ => Ok(KElement::Text(symbol.to_string()))
         
             // MINUS => KElement::Minus
         }
     }
 
-    pub fn rasterize_symbol(&self, globals: &mut RusTeX, current_scale: f32) -> Bitmap {
-        match self {
-            KElement::Plus => Self::render_text_block(&mut globals.layout, PLUS, 0, current_scale, OPERATOR_X_PADDING),
-            KElement::Minus => Self::render_text_block(&mut globals.layout, MINUS, 0, current_scale, OPERATOR_X_PADDING),
-            KElement::Equals => Self::render_text_block(&mut globals.layout, EQUALS, 0, current_scale, OPERATOR_X_PADDING),
-            KElement::PlusMinus => Self::render_text_block(&mut globals.layout, PLUS_MINUS, 0, current_scale, OPERATOR_X_PADDING),
-            _ => unreachable!()
-        }
-    }
+    /// Lays out an operator with its ink centered on the math axis
+    pub(crate) fn layout_symbol(globals: &mut LayoutContext<'_>, symbol: &str, current_scale: f32) -> LayoutBox {
+        let mut symbol = Self::layout_text(&mut globals.layout, symbol, 0, current_scale, OPERATOR_X_PADDING, globals.settings.foreground);
 
-    pub fn get_symbol_bounds(&self, globals: &mut RusTeX, current_scale: f32) -> (usize, usize, usize) {
-        match self {
-            KElement::Plus => Self::measure_text_bounds(&mut globals.layout, PLUS, 0, current_scale, OPERATOR_X_PADDING, PLUS_YOFFSET),
-            KElement::Minus => Self::measure_text_bounds(&mut globals.layout, MINUS, 0, current_scale, OPERATOR_X_PADDING, MINUS_YOFFSET),
-            KElement::Equals => Self::measure_text_bounds(&mut globals.layout, EQUALS, 0, current_scale, OPERATOR_X_PADDING, EQUALS_YOFFSET),
-            KElement::PlusMinus => Self::measure_text_bounds(&mut globals.layout, PLUS_MINUS, 0, current_scale, OPERATOR_X_PADDING, PLUSMMINUS_YOFFSET),
-            _ => unreachable!()
+        if let LayoutContent::Glyphs { glyphs, .. } = &symbol.content {
+            let top = glyphs.iter().map(|glyph| glyph.y).fold(f32::INFINITY, f32::min);
+            let bottom = glyphs.iter().map(|glyph| glyph.y + glyph.height as f32).fold(f32::NEG_INFINITY, f32::max);
+
+            // Moving the baseline keeps the glyphs where they are in the box
+            let total_height = symbol.total_height();
+            let baseline = (top + bottom)/2. + *AXIS_HEIGHT*current_scale;
+            symbol.height = baseline;
+            symbol.depth = (total_height - baseline).max(0.);
         }
+
+        symbol
     }
 }
\ No newline at end of file
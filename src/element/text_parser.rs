@@ -157,19 +157,17 @@
 
 
 
-use regex::Regex;
-use lazy_static::lazy_static;
 use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
 
-lazy_static! {
-    static ref FUNCTION_REGEX: Regex = Regex::new(r"\\[a-zA-Z]+").unwrap();
-    static ref NUMBER_REGEX: Regex = Regex::new(r"\d+(?:\.\d+)?").unwrap();
-    static ref LETTER_REGEX: Regex =  Regex::new(r"[a-zA-Z]").unwrap();
-    static ref OPERATOR_REGEX: Regex = Regex::new(r"[+\-=*/±]").unwrap();
-    static ref WHITESPACE_REGEX: Regex = Regex::new(r"\s+").unwrap();
-}
+use crate::element::parse_error::{ParseError, ParseErrorKind, Span};
 
-#[derive(Debug, Clone, PartialEq)]
+/// Deepest nesting of braces, parentheses and function arguments accepted. Parsing, layout and
+/// rendering all recurse once per level, so this keeps them well within a thread's stack.
+pub(crate) const MAX_NESTING: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PerenthesisType {
     Round,    // ()
     Square,   // []
@@ -177,27 +175,68 @@ pub enum PerenthesisType {
     // Angle,    // <>
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl PerenthesisType {
+    pub(crate) fn delimiters(&self) -> (char, char) {
+        match self {
+            PerenthesisType::Round => ('(', ')'),
+            PerenthesisType::Square => ('[', ']'),
+        }
+    }
+}
+
+/// Objects span their source from the first token to the last, including scripts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ParsedObject {
     Func {
         name: String,
         content: Vec<Vec<ParsedObject>>,
+        #[serde(default)]
         super_script: Vec<ParsedObject>,
+        #[serde(default)]
         sub_script: Vec<ParsedObject>,
+        #[serde(default)]
+        span: Span,
     },
     Var {
         text: String,
+        #[serde(default)]
         super_script: Vec<ParsedObject>,
+        #[serde(default)]
         sub_script: Vec<ParsedObject>,
+        #[serde(default)]
+        span: Span,
     },
     Operator {
         text: String,
+        #[serde(default)]
+        span: Span,
     },
     Parenthesis {
         inner: Vec<ParsedObject>,
         parenthesis_type: PerenthesisType,
+        #[serde(default)]
         super_script: Vec<ParsedObject>,
+        #[serde(default)]
         sub_script: Vec<ParsedObject>,
+        #[serde(default)]
+        span: Span,
+    },
+    /// A braced group, which keeps style switches inside it and can carry scripts as a whole
+    Group {
+        inner: Vec<ParsedObject>,
+        #[serde(default)]
+        super_script: Vec<ParsedObject>,
+        #[serde(default)]
+        sub_script: Vec<ParsedObject>,
+        #[serde(default)]
+        span: Span,
+    },
+    /// Source that failed to parse in lenient mode
+    Error {
+        text: String,
+        #[serde(default)]
+        span: Span,
     },
 }
 
@@ -213,122 +252,253 @@ enum Token {
     RightBrace,
     Superscript,
     Subscript,
+    /// A character that starts no token
+    Invalid(char),
 }
 
-pub fn parse(input: &str) -> Result<Vec<ParsedObject>, String> {
-    let tokens = tokenize_with_regex(input)?;
-    let mut token_queue = VecDeque::from(tokens);
-    parse_tokens(&mut token_queue)
+impl Token {
+    /// How the token is referred to in error messages
+    fn describe(&self) -> String {
+        match self {
+            Token::Function(name) => format!("`\\{}`", name),
+            Token::Number(text) | Token::Letter(text) | Token::Operator(text) => format!("`{}`", text),
+            Token::LeftParen(paren_type) => format!("`{}`", paren_type.delimiters().0),
+            Token::RightParen(paren_type) => format!("`{}`", paren_type.delimiters().1),
+            Token::LeftBrace => "`{`".to_string(),
+            Token::RightBrace => "`}`".to_string(),
+            Token::Superscript => "`^`".to_string(),
+            Token::Subscript => "`_`".to_string(),
+            Token::Invalid(character) => format!("`{}`", character),
+        }
+    }
+
+    /// Error for this token found at `span` where it doesn't belong
+    fn unexpected(&self, span: Span, expected: Option<&str>) -> ParseError {
+        ParseError::new(ParseErrorKind::UnexpectedToken { expected: expected.map(str::to_string), found: self.describe() }, span)
+    }
+
+    /// Whether the token can start an object. Anything else ends a run of objects.
+    fn starts_object(&self) -> bool {
+        matches!(self, Token::Function(_) | Token::Number(_) | Token::Letter(_) | Token::Operator(_) | Token::LeftParen(_) | Token::LeftBrace | Token::Invalid(_))
+    }
 }
 
-fn tokenize_with_regex(input: &str) -> Result<Vec<Token>, String> {
-    let mut tokens = Vec::new();
-    let mut pos = 0;
-    let input_chars: Vec<char> = input.chars().collect();
+/// Tokens with their source spans, plus the source for errors and error recovery
+struct TokenStream<'a> {
+    tokens: VecDeque<(Token, Span)>,
+    source: &'a str,
+    /// End of the last consumed token
+    consumed_until: usize,
+    /// Turn unparsable source into `ParsedObject::Error` instead of failing
+    lenient: bool,
+    /// Problems recovered from in lenient mode
+    errors: Vec<ParseError>,
+    /// Number of groups the parser is currently inside
+    depth: usize,
+}
 
-    while pos < input_chars.len() {
-        let remaining: String = input_chars[pos..].iter().collect();
-        
-        // Skip whitespace
-        if let Some(mat) = WHITESPACE_REGEX.find(&remaining) {
-            if mat.start() == 0 {
-                pos += mat.len();
-                continue;
-            }
+impl TokenStream<'_> {
+    fn new(input: &str, lenient: bool) -> TokenStream<'_> {
+        TokenStream {
+            tokens: VecDeque::from(tokenize(input)),
+            source: input,
+            consumed_until: 0,
+            lenient,
+            errors: Vec::new(),
+            depth: 0,
         }
+    }
 
-        // Check for TeX functions
-        if let Some(mat) = FUNCTION_REGEX.find(&remaining) {
-            if mat.start() == 0 {
-                let func_name = mat.as_str()[1..].to_string(); // Remove the \
-                tokens.push(Token::Function(func_name));
-                pos += mat.len();
-                continue;
-            }
+    fn front(&self) -> Option<&Token> {
+        self.tokens.front().map(|(token, _)| token)
+    }
+
+    /// Consumes the next token if `accept` takes it
+    fn next_if(&mut self, accept: impl FnOnce(&Token) -> bool) -> Option<(Token, Span)> {
+        if self.front().is_some_and(accept) {
+            self.pop_front()
+        } else {
+            None
         }
+    }
 
-        // Check for numbers (including decimals)
-        if let Some(mat) = NUMBER_REGEX.find(&remaining) {
-            if mat.start() == 0 {
-                tokens.push(Token::Number(mat.as_str().to_string()));
-                pos += mat.len();
-                continue;
-            }
+    fn pop_front(&mut self) -> Option<(Token, Span)> {
+        let next = self.tokens.pop_front();
+        if let Some((_, span)) = &next {
+            self.consumed_until = span.end;
         }
+        next
+    }
 
-        // Check for single letters
-        if let Some(mat) = LETTER_REGEX.find(&remaining) {
-            if mat.start() == 0 {
-                tokens.push(Token::Letter(mat.as_str().to_string()));
-                pos += mat.len();
-                continue;
-            }
+    /// Span of the next token, or an empty span at the end of the input
+    fn next_span(&self) -> Span {
+        self.tokens.front().map_or(Span::new(self.source.len(), self.source.len()), |(_, span)| *span)
+    }
+
+    /// Error for the next token, or for the end of the input
+    fn unexpected(&self, expected: Option<&str>) -> ParseError {
+        match self.front() {
+            Some(token) => token.unexpected(self.next_span(), expected),
+            None => ParseError::new(
+                ParseErrorKind::UnexpectedEnd { expected: expected.unwrap_or("more input").to_string() },
+                self.next_span()
+            ),
         }
+    }
 
-        // Check for operators
-        if let Some(mat) = OPERATOR_REGEX.find(&remaining) {
-            if mat.start() == 0 {
-                tokens.push(Token::Operator(mat.as_str().to_string()));
-                pos += mat.len();
-                continue;
-            }
+    /// Records `error` and skips past it, returning the source from `start` up to the error as an error object
+    fn recover(&mut self, error: ParseError, start: usize) -> ParsedObject {
+        while self.tokens.front().is_some_and(|(_, span)| span.start < error.span.end) {
+            self.pop_front();
         }
+        self.errors.push(error);
 
-        // Handle special characters
-        match input_chars[pos] {
-            '(' => tokens.push(Token::LeftParen(PerenthesisType::Round)),
-            ')' => tokens.push(Token::RightParen(PerenthesisType::Round)),
-            '[' => tokens.push(Token::LeftParen(PerenthesisType::Square)),
-            ']' => tokens.push(Token::RightParen(PerenthesisType::Square)),
-            '{' => tokens.push(Token::LeftBrace),
-            '}' => tokens.push(Token::RightBrace),
-            '^' => tokens.push(Token::Superscript),
-            '_' => tokens.push(Token::Subscript),
-            _ => return Err(format!("Unexpected character: '{}'", input_chars[pos])),
+        let span = Span::new(start, self.consumed_until.max(start));
+        ParsedObject::Error {
+            text: self.source[span.start..span.end].to_string(),
+            span,
         }
-        
-        pos += 1;
+    }
+}
+
+pub fn parse(input: &str) -> Result<Vec<ParsedObject>, ParseError> {
+    let mut tokens = TokenStream::new(input, false);
+    let result = parse_tokens(&mut tokens)?;
+
+    // parse_tokens stops at anything that can't start an object, which is only valid inside a group
+    if tokens.front().is_some() {
+        return Err(tokens.unexpected(None));
     }
 
-    Ok(tokens)
+    Ok(result)
 }
 
-fn parse_tokens(tokens: &mut VecDeque<Token>) -> Result<Vec<ParsedObject>, String> {
+/// Parses as much as possible, turning each unparsable part into a `ParsedObject::Error` and
+/// returning the problems alongside the objects
+pub fn parse_lenient(input: &str) -> (Vec<ParsedObject>, Vec<ParseError>) {
+    let mut tokens = TokenStream::new(input, true);
     let mut result = Vec::new();
 
-    while let Some(token) = tokens.pop_front() {
-        match token {
-            Token::Function(name) => {
-                let func = parse_function_generic(name, tokens)?;
-                result.push(func);
-            }
-            Token::Number(text) | Token::Letter(text) => {
-                let var = parse_variable(text, tokens)?;
-                result.push(var);
-            }
-            Token::Operator(op) => {
-                result.push(ParsedObject::Operator { text: op });
-            }
-            Token::LeftParen(paren_type) => {
-                let paren = parse_parenthesis(paren_type, tokens)?;
-                result.push(paren);
-            }
-            Token::RightParen(_) | Token::RightBrace => {
-                // Put it back - should be handled by parent context
-                tokens.push_front(token);
-                break;
+    loop {
+        // Errors are always recovered in lenient mode
+        result.extend(parse_tokens(&mut tokens).unwrap_or_default());
+
+        if tokens.front().is_none() {
+            break;
+        }
+        let error = tokens.unexpected(None);
+        let start = error.span.start;
+        result.push(tokens.recover(error, start));
+    }
+
+    (result, tokens.errors)
+}
+
+/// Splits the input into tokens in a single pass. Characters that start no token become
+/// `Token::Invalid`, which the parser reports.
+fn tokenize(input: &str) -> Vec<(Token, Span)> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while let Some(character) = input[pos..].chars().next() {
+        let start = pos;
+        pos += character.len_utf8();
+
+        let token = match character {
+            c if c.is_whitespace() => continue,
+            // TeX functions, a backslash followed by letters
+            '\\' if bytes.get(pos).is_some_and(u8::is_ascii_alphabetic) => {
+                pos = skip_while(bytes, pos, u8::is_ascii_alphabetic);
+                Token::Function(input[start + 1..pos].to_string()) // Remove the \
             }
-            _ => {
-                tokens.push_front(token);
-                break;
+            // Numbers, with a fraction part only when digits follow the point
+            '0'..='9' => {
+                pos = skip_while(bytes, pos, u8::is_ascii_digit);
+                if bytes.get(pos) == Some(&b'.') && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit) {
+                    pos = skip_while(bytes, pos + 1, u8::is_ascii_digit);
+                }
+                Token::Number(input[start..pos].to_string())
             }
+            'a'..='z' | 'A'..='Z' => Token::Letter(character.to_string()),
+            '+' | '-' | '=' | '*' | '/' | '±' => Token::Operator(character.to_string()),
+            '(' => Token::LeftParen(PerenthesisType::Round),
+            ')' => Token::RightParen(PerenthesisType::Round),
+            '[' => Token::LeftParen(PerenthesisType::Square),
+            ']' => Token::RightParen(PerenthesisType::Square),
+            '{' => Token::LeftBrace,
+            '}' => Token::RightBrace,
+            '^' => Token::Superscript,
+            '_' => Token::Subscript,
+            _ => Token::Invalid(character),
+        };
+
+        tokens.push((token, Span::new(start, pos)));
+    }
+
+    tokens
+}
+
+/// Index of the first byte at or after `from` that doesn't match `predicate`
+fn skip_while(bytes: &[u8], from: usize, predicate: impl Fn(&u8) -> bool) -> usize {
+    from + bytes[from..].iter().take_while(|b| predicate(b)).count()
+}
+
+fn parse_tokens(tokens: &mut TokenStream) -> Result<Vec<ParsedObject>, ParseError> {
+    if tokens.depth > MAX_NESTING {
+        return Err(skip_nested(tokens));
+    }
+
+    tokens.depth += 1;
+    let result = parse_objects(tokens);
+    tokens.depth -= 1;
+    result
+}
+
+/// Skips the rest of a group nested too deeply, including its closing delimiter, and returns the
+/// error covering it. Lenient parsing then turns the whole group into a single error node.
+fn skip_nested(tokens: &mut TokenStream) -> ParseError {
+    let start = tokens.next_span().start;
+    let mut open = 0usize;
+
+    while let Some((token, _)) = tokens.pop_front() {
+        match token {
+            Token::LeftBrace | Token::LeftParen(_) => open += 1,
+            Token::RightBrace | Token::RightParen(_) if open == 0 => break,
+            Token::RightBrace | Token::RightParen(_) => open -= 1,
+            _ => {}
+        }
+    }
+
+    ParseError::new(ParseErrorKind::NestingTooDeep { limit: MAX_NESTING }, Span::new(start, tokens.consumed_until))
+}
+
+fn parse_objects(tokens: &mut TokenStream) -> Result<Vec<ParsedObject>, ParseError> {
+    let mut result = Vec::new();
+
+    // Anything that can't start an object is left for the parent context
+    while let Some((token, span)) = tokens.next_if(Token::starts_object) {
+        let parsed = match token {
+            Token::Function(name) => parse_function_generic(name, span, tokens),
+            Token::Number(text) | Token::Letter(text) => parse_variable(text, span, tokens),
+            Token::Operator(op) => Ok(ParsedObject::Operator { text: op, span }),
+            Token::LeftParen(paren_type) => parse_parenthesis(paren_type, span, tokens),
+            Token::LeftBrace => parse_group(span, tokens),
+            Token::Invalid(character) => Err(ParseError::new(ParseErrorKind::UnexpectedCharacter { found: character }, span)),
+            token => Err(token.unexpected(span, None)),
+        };
+
+        match parsed {
+            Ok(object) => result.push(object),
+            Err(error) if tokens.lenient => result.push(tokens.recover(error, span.start)),
+            Err(error) => return Err(error),
         }
     }
 
     Ok(result)
 }
 
-fn parse_function_generic(name: String, tokens: &mut VecDeque<Token>) -> Result<ParsedObject, String> {
+fn parse_function_generic(name: String, start: Span, tokens: &mut TokenStream) -> Result<ParsedObject, ParseError> {
     let mut content = Vec::new();
     
     // Parse all braced content that follows this function
@@ -345,37 +515,32 @@ fn parse_function_generic(name: String, tokens: &mut VecDeque<Token>) -> Result<
         content,
         super_script,
         sub_script,
+        span: Span::new(start.start, tokens.consumed_until),
     })
 }
 
-fn parse_variable(text: String, tokens: &mut VecDeque<Token>) -> Result<ParsedObject, String> {
+fn parse_variable(text: String, start: Span, tokens: &mut TokenStream) -> Result<ParsedObject, ParseError> {
     let (super_script, sub_script) = parse_scripts(tokens)?;
 
     Ok(ParsedObject::Var {
         text,
         super_script,
         sub_script,
+        span: Span::new(start.start, tokens.consumed_until),
     })
 }
 
-fn parse_parenthesis(paren_type: PerenthesisType, tokens: &mut VecDeque<Token>) -> Result<ParsedObject, String> {
-    let mut inner = Vec::new();
+fn parse_parenthesis(paren_type: PerenthesisType, start: Span, tokens: &mut TokenStream) -> Result<ParsedObject, ParseError> {
+    let (opening, closing) = paren_type.delimiters();
+    let inner = parse_tokens(tokens)?;
 
-    // Parse until we find the matching closing parenthesis
-    while !tokens.is_empty() {
-        if let Some(Token::RightParen(closing_type)) = tokens.front() {
-            if *closing_type == paren_type {
-                tokens.pop_front(); // consume the closing paren
-                break;
-            }
-        }
-        
-        let parsed = parse_tokens(tokens)?;
-        inner.extend(parsed);
-        
-        if tokens.is_empty() {
-            return Err("Unmatched opening parenthesis".to_string());
+    // Parsing stopped at the closing parenthesis, or at something that doesn't belong here
+    match tokens.front() {
+        Some(Token::RightParen(closing_type)) if *closing_type == paren_type => {
+            tokens.pop_front(); // consume the closing paren
         }
+        Some(_) => return Err(tokens.unexpected(Some(&format!("`{}`", closing)))),
+        None => return Err(ParseError::new(ParseErrorKind::UnclosedDelimiter { delimiter: opening }, start)),
     }
 
     let (super_script, sub_script) = parse_scripts(tokens)?;
@@ -385,82 +550,182 @@ fn parse_parenthesis(paren_type: PerenthesisType, tokens: &mut VecDeque<Token>)
         parenthesis_type: paren_type,
         super_script,
         sub_script,
+        span: Span::new(start.start, tokens.consumed_until),
     })
 }
 
-fn parse_braced_content(tokens: &mut VecDeque<Token>) -> Result<Vec<ParsedObject>, String> {
-    if let Some(Token::LeftBrace) = tokens.pop_front() {
-        let mut content = Vec::new();
+fn parse_group(start: Span, tokens: &mut TokenStream) -> Result<ParsedObject, ParseError> {
+    let inner = parse_until_closing_brace(start, tokens)?;
+    let (super_script, sub_script) = parse_scripts(tokens)?;
 
-        while !tokens.is_empty() {
-            if let Some(Token::RightBrace) = tokens.front() {
-                tokens.pop_front(); // consume the closing brace
-                break;
-            }
-            
-            let parsed = parse_tokens(tokens)?;
-            content.extend(parsed);
-            
-            if tokens.is_empty() {
-                return Err("Unmatched opening brace".to_string());
-            }
-        }
+    Ok(ParsedObject::Group {
+        inner,
+        super_script,
+        sub_script,
+        span: Span::new(start.start, tokens.consumed_until),
+    })
+}
+
+fn parse_braced_content(tokens: &mut TokenStream) -> Result<Vec<ParsedObject>, ParseError> {
+    let Some((_, start)) = tokens.next_if(|token| *token == Token::LeftBrace) else {
+        return Err(tokens.unexpected(Some("`{`")));
+    };
+
+    parse_until_closing_brace(start, tokens)
+}
+
+/// Parses the content of a group whose opening brace at `start` was just consumed, and its closing brace
+fn parse_until_closing_brace(start: Span, tokens: &mut TokenStream) -> Result<Vec<ParsedObject>, ParseError> {
+    let content = parse_tokens(tokens)?;
 
-        Ok(content)
-    } else {
-        Err("Expected opening brace".to_string())
+    // Parsing stopped at the closing brace, or at something that doesn't belong here
+    match tokens.front() {
+        Some(Token::RightBrace) => {
+            tokens.pop_front(); // consume the closing brace
+            Ok(content)
+        }
+        Some(_) => Err(tokens.unexpected(Some("`}`"))),
+        None => Err(ParseError::new(ParseErrorKind::UnclosedDelimiter { delimiter: '{' }, start)),
     }
 }
 
-fn parse_scripts(tokens: &mut VecDeque<Token>) -> Result<(Vec<ParsedObject>, Vec<ParsedObject>), String> {
-    let mut super_script = Vec::new();
-    let mut sub_script = Vec::new();
+fn parse_scripts(tokens: &mut TokenStream) -> Result<(Vec<ParsedObject>, Vec<ParsedObject>), ParseError> {
+    let mut super_script = None;
+    let mut sub_script = None;
 
     // Parse superscript and subscript (can appear in any order)
-    while let Some(token) = tokens.front() {
-        match token {
-            Token::Superscript => {
-                tokens.pop_front(); // consume ^
-                super_script = parse_script_content(tokens)?;
-            }
-            Token::Subscript => {
-                tokens.pop_front(); // consume _
-                sub_script = parse_script_content(tokens)?;
-            }
-            _ => break,
+    while let Some((token, span)) = tokens.next_if(|token| matches!(token, Token::Superscript | Token::Subscript)) {
+        let (script, character) = if token == Token::Superscript {
+            (&mut super_script, '^')
+        } else {
+            (&mut sub_script, '_')
+        };
+
+        if script.is_some() {
+            return Err(ParseError::new(ParseErrorKind::DoubleScript { script: character }, span));
         }
+        *script = Some(parse_script_content(tokens)?);
     }
 
-    Ok((super_script, sub_script))
+    Ok((super_script.unwrap_or_default(), sub_script.unwrap_or_default()))
 }
 
-fn parse_script_content(tokens: &mut VecDeque<Token>) -> Result<Vec<ParsedObject>, String> {
-    if let Some(token) = tokens.front() {
-        match token {
-            Token::LeftBrace => {
-                // Multi-character script content in braces
-                parse_braced_content(tokens)
-            }
-            Token::Number(text) | Token::Letter(text) => {
-                // Single character/number script content
-                let text = text.clone();
-                tokens.pop_front();
-                Ok(vec![ParsedObject::Var {
-                    text,
-                    super_script: vec![],
-                    sub_script: vec![],
-                }])
-            }
-            Token::Function(name) => {
-                // Function in script
-                let name = name.clone();
-                tokens.pop_front();
-                let func = parse_function_generic(name, tokens)?;
-                Ok(vec![func])
-            }
-            _ => Err("Invalid script content".to_string()),
+fn parse_script_content(tokens: &mut TokenStream) -> Result<Vec<ParsedObject>, ParseError> {
+    if tokens.front() == Some(&Token::LeftBrace) {
+        // Multi-character script content in braces
+        return parse_braced_content(tokens);
+    }
+
+    match tokens.next_if(|token| matches!(token, Token::Number(_) | Token::Letter(_) | Token::Function(_))) {
+        Some((Token::Number(text) | Token::Letter(text), span)) => {
+            // Single character/number script content
+            Ok(vec![ParsedObject::Var {
+                text,
+                super_script: vec![],
+                sub_script: vec![],
+                span,
+            }])
         }
-    } else {
-        Err("Expected script content".to_string())
+        Some((Token::Function(name), span)) => {
+            // Function in script
+            let func = parse_function_generic(name, span, tokens)?;
+            Ok(vec![func])
+        }
+        Some((token, span)) => Err(token.unexpected(span, Some("script content"))),
+        None => Err(tokens.unexpected(Some("script content"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(input: &str) -> Vec<(Token, (usize, usize))> {
+        tokenize(input).into_iter().map(|(token, span)| (token, (span.start, span.end))).collect()
+    }
+
+    #[test]
+    fn tokens_carry_their_byte_spans() {
+        assert_eq!(spans(r"\frac{x}  {12.5}^2_"), [
+            (Token::Function("frac".to_string()), (0, 5)),
+            (Token::LeftBrace, (5, 6)),
+            (Token::Letter("x".to_string()), (6, 7)),
+            (Token::RightBrace, (7, 8)),
+            (Token::LeftBrace, (10, 11)),
+            (Token::Number("12.5".to_string()), (11, 15)),
+            (Token::RightBrace, (15, 16)),
+            (Token::Superscript, (16, 17)),
+            (Token::Number("2".to_string()), (17, 18)),
+            (Token::Subscript, (18, 19)),
+        ]);
+    }
+
+    #[test]
+    fn numbers_only_take_a_point_followed_by_digits() {
+        assert_eq!(spans("3.x"), [
+            (Token::Number("3".to_string()), (0, 1)),
+            (Token::Invalid('.'), (1, 2)),
+            (Token::Letter("x".to_string()), (2, 3)),
+        ]);
+    }
+
+    #[test]
+    fn multi_byte_characters_span_all_their_bytes() {
+        assert_eq!(spans("a±é\u{3000}b"), [
+            (Token::Letter("a".to_string()), (0, 1)),
+            (Token::Operator("±".to_string()), (1, 3)),
+            (Token::Invalid('é'), (3, 5)),
+            // U+3000 is an ideographic space, three bytes long
+            (Token::Letter("b".to_string()), (8, 9)),
+        ]);
+    }
+
+    #[test]
+    fn errors_point_at_multi_byte_characters() {
+        let error = parse("x + é").unwrap_err();
+        assert_eq!(error.span, Span::new(4, 6));
+        assert_eq!(&"x + é"[error.span.start..error.span.end], "é");
+    }
+
+    #[test]
+    fn a_backslash_without_letters_is_invalid() {
+        assert_eq!(spans(r"\ 1"), [
+            (Token::Invalid('\\'), (0, 1)),
+            (Token::Number("1".to_string()), (2, 3)),
+        ]);
+    }
+
+    #[test]
+    fn nesting_deeper_than_the_limit_is_an_error() {
+        let nested = |depth| format!("{}x{}", "{".repeat(depth), "}".repeat(depth));
+        assert!(parse(&nested(MAX_NESTING)).is_ok());
+
+        let source = nested(3000);
+        let error = parse(&source).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::NestingTooDeep { limit: MAX_NESTING });
+        assert_eq!(error.code(), "E0009");
+    }
+
+    #[test]
+    fn lenient_parsing_skips_a_group_nested_too_deeply() {
+        let source = format!("a+{}x{}+b", "(".repeat(3000), ")".repeat(3000));
+        let (objects, errors) = parse_lenient(&source);
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(objects.last(), Some(ParsedObject::Var { text, .. }) if text == "b"));
+    }
+
+    #[test]
+    fn lenient_parsing_keeps_what_comes_before_an_unclosed_brace() {
+        let (objects, errors) = parse_lenient("x+{a+b");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ParseErrorKind::UnclosedDelimiter { delimiter: '{' });
+        assert_eq!(errors[0].span, Span::new(2, 3));
+        assert!(matches!(objects.as_slice(), [
+            ParsedObject::Var { text: x, .. },
+            ParsedObject::Operator { text: plus, .. },
+            ParsedObject::Error { text: error, .. },
+        ] if x == "x" && plus == "+" && error == "{a+b"));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,186 @@
+use crate::element::KElement;
+
+const SUPERSCRIPTS: &[(char, char)] = &[
+    ('0', '⁰'), ('1', '¹'), ('2', '²'), ('3', '³'), ('4', '⁴'), ('5', '⁵'), ('6', '⁶'), ('7', '⁷'), ('8', '⁸'), ('9', '⁹'),
+    ('+', '⁺'), ('−', '⁻'), ('=', '⁼'), ('(', '⁽'), (')', '⁾'),
+    ('a', 'ᵃ'), ('b', 'ᵇ'), ('c', 'ᶜ'), ('d', 'ᵈ'), ('e', 'ᵉ'), ('f', 'ᶠ'), ('g', 'ᵍ'), ('h', 'ʰ'), ('i', 'ⁱ'),
+    ('j', 'ʲ'), ('k', 'ᵏ'), ('l', 'ˡ'), ('m', 'ᵐ'), ('n', 'ⁿ'), ('o', 'ᵒ'), ('p', 'ᵖ'), ('r', 'ʳ'), ('s', 'ˢ'),
+    ('t', 'ᵗ'), ('u', 'ᵘ'), ('v', 'ᵛ'), ('w', 'ʷ'), ('x', 'ˣ'), ('y', 'ʸ'), ('z', 'ᶻ'),
+];
+
+const SUBSCRIPTS: &[(char, char)] = &[
+    ('0', '₀'), ('1', '₁'), ('2', '₂'), ('3', '₃'), ('4', '₄'), ('5', '₅'), ('6', '₆'), ('7', '₇'), ('8', '₈'), ('9', '₉'),
+    ('+', '₊'), ('−', '₋'), ('=', '₌'), ('(', '₍'), (')', '₎'),
+    ('a', 'ₐ'), ('e', 'ₑ'), ('h', 'ₕ'), ('i', 'ᵢ'), ('j', 'ⱼ'), ('k', 'ₖ'), ('l', 'ₗ'), ('m', 'ₘ'), ('n', 'ₙ'),
+    ('o', 'ₒ'), ('p', 'ₚ'), ('r', 'ᵣ'), ('s', 'ₛ'), ('t', 'ₜ'), ('u', 'ᵤ'), ('v', 'ᵥ'), ('x', 'ₓ'),
+];
+
+const VULGAR_FRACTIONS: &[((i64, i64), char)] = &[
+    ((1, 2), '½'), ((1, 3), '⅓'), ((2, 3), '⅔'), ((1, 4), '¼'), ((3, 4), '¾'), ((1, 5), '⅕'), ((2, 5), '⅖'),
+    ((3, 5), '⅗'), ((4, 5), '⅘'), ((1, 6), '⅙'), ((5, 6), '⅚'), ((1, 7), '⅐'), ((1, 8), '⅛'), ((3, 8), '⅜'),
+    ((5, 8), '⅝'), ((7, 8), '⅞'), ((1, 9), '⅑'), ((1, 10), '⅒'),
+];
+
+/// U+2044 FRACTION SLASH, used between superscript and subscript digits
+const FRACTION_SLASH: char = '⁄';
+
+impl KElement {
+    /// Renders the element as a single line of Unicode text, e.g. `𝑥² + 𝑦₁ = ½`.
+    ///
+    /// Variables use Mathematical Alphanumeric italics and scripts use Unicode super/subscript
+    /// characters where they exist. Anything without a Unicode form falls back to linear
+    /// notation such as `(a+b)/(c+d)` or `x^(n+1)`.
+    pub fn to_unicode(&self) -> String {
+        self.unicode_text(true, true)
+    }
+
+    /// `italic` maps letters to Mathematical Alphanumerics, `spaced` puts spaces around binary operators
+    fn unicode_text(&self, italic: bool, spaced: bool) -> String {
+        match self {
+            KElement::LinearGroup(elems) => {
+                let mut text = String::new();
+                for (i, elem) in elems.iter().enumerate() {
+                    // An operator at the start or after another operator is unary, e.g. -x
+                    let binary = i > 0 && !elems[i - 1].is_operator();
+                    if elem.is_operator() && binary && spaced {
+                        text.push(' ');
+                        text.push_str(&elem.unicode_text(italic, spaced));
+                        text.push(' ');
+                    } else {
+                        text.push_str(&elem.unicode_text(italic, spaced));
+                    }
+                }
+                text
+            }
+            KElement::Integer(i) => i.to_string(),
+            KElement::Decimal(d) => d.to_string(),
+            KElement::Text(text) => {
+                if italic {
+                    text.chars().map(math_italic).collect()
+                } else {
+                    text.clone()
+                }
+            }
+            KElement::Error(source) => source.clone(),
+            KElement::Fraction { upper, lower } => {
+                if let (Some(n), Some(d)) = (upper.as_integer(), lower.as_integer()) {
+                    if let Some((_, c)) = VULGAR_FRACTIONS.iter().find(|(f, _)| *f == (n, d)) {
+                        return c.to_string();
+                    }
+                    if n >= 0 && d > 0 {
+                        let (n, d) = (n.to_string(), d.to_string());
+                        if let (Some(n), Some(d)) = (map_chars(&n, SUPERSCRIPTS), map_chars(&d, SUBSCRIPTS)) {
+                            return format!("{}{}{}", n, FRACTION_SLASH, d);
+                        }
+                    }
+                }
+
+                format!("{}/{}", upper.linear_operand(italic), lower.linear_operand(italic))
+            }
+            KElement::SuperSub { inner, upper, lower } => {
+                let mut text = inner.linear_operand(italic);
+                if let Some(lower) = lower {
+                    text.push_str(&lower.script_text(SUBSCRIPTS, '_', italic));
+                }
+                if let Some(upper) = upper {
+                    text.push_str(&upper.script_text(SUPERSCRIPTS, '^', italic));
+                }
+                text
+            }
+            KElement::Delimited { open, close, inner } => format!("{}{}{}", open, inner.unicode_text(italic, spaced), close),
+            // Plain text has a single size
+            KElement::Styled { inner, .. } => inner.unicode_text(italic, spaced),
+            KElement::Plus => "+".to_string(),
+            KElement::Minus => "−".to_string(),
+            KElement::Equals => "=".to_string(),
+            KElement::PlusMinus => "±".to_string(),
+        }
+    }
+
+    /// Unicode script characters if every character has one, otherwise `^x` or `^(...)`
+    fn script_text(&self, table: &[(char, char)], marker: char, italic: bool) -> String {
+        match map_chars(&self.unicode_text(false, false), table) {
+            Some(script) => script,
+            None => format!("{}{}", marker, self.linear_operand(italic)),
+        }
+    }
+
+    /// Text of the element wrapped in parentheses unless it is a single atom or character
+    fn linear_operand(&self, italic: bool) -> String {
+        let text = self.unicode_text(italic, false);
+        if self.is_atom() || text.chars().count() == 1 {
+            text
+        } else {
+            format!("({})", text)
+        }
+    }
+
+    pub(super) fn is_atom(&self) -> bool {
+        match self {
+            KElement::LinearGroup(elems) => elems.len() <= 1 && elems.iter().all(KElement::is_atom),
+            KElement::Integer(_) | KElement::Decimal(_) => true,
+            KElement::Text(text) => text.chars().count() == 1,
+            KElement::SuperSub { .. } | KElement::Delimited { .. } => true,
+            _ => false,
+        }
+    }
+
+    pub(super) fn is_operator(&self) -> bool {
+        matches!(self, KElement::Plus | KElement::Minus | KElement::Equals | KElement::PlusMinus)
+    }
+
+    fn as_integer(&self) -> Option<i64> {
+        match self {
+            KElement::LinearGroup(elems) if elems.len() == 1 => elems[0].as_integer(),
+            KElement::Integer(i) => Some(*i),
+            KElement::Decimal(d) if d.fract() == 0. && d.abs() < i64::MAX as f64 => Some(*d as i64),
+            _ => None,
+        }
+    }
+}
+
+fn map_chars(text: &str, table: &[(char, char)]) -> Option<String> {
+    text.chars()
+        .map(|c| table.iter().find(|(from, _)| *from == c).map(|(_, to)| *to))
+        .collect()
+}
+
+/// Maps ASCII letters to Mathematical Italic (U+1D434 onwards), leaving everything else unchanged
+fn math_italic(c: char) -> char {
+    match c {
+        // Italic small h lives in the Letterlike Symbols block, its Mathematical Alphanumeric slot is reserved
+        'h' => 'ℎ',
+        'a'..='z' => char::from_u32(0x1D44E + (c as u32 - 'a' as u32)).unwrap_or(c),
+        'A'..='Z' => char::from_u32(0x1D434 + (c as u32 - 'A' as u32)).unwrap_or(c),
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::element::KElement;
+
+    fn unicode(source: &str) -> String {
+        KElement::parse(source).unwrap().to_unicode()
+    }
+
+    #[test]
+    fn scripts_use_script_characters() {
+        assert_eq!(unicode("x^2"), "𝑥²");
+        assert_eq!(unicode("y_1"), "𝑦₁");
+        assert_eq!(unicode("a_i^2"), "𝑎ᵢ²");
+        assert_eq!(unicode("(a+b)^{n+1}"), "(𝑎+𝑏)ⁿ⁺¹");
+    }
+
+    #[test]
+    fn simple_fractions_use_vulgar_fractions() {
+        assert_eq!(unicode(r"x^2+y_1=\frac{1}{2}"), "𝑥² + 𝑦₁ = ½");
+    }
+
+    #[test]
+    fn constructs_without_a_unicode_form_fall_back_to_linear_notation() {
+        assert_eq!(unicode(r"\frac{a+b}{c+d}"), "(𝑎+𝑏)/(𝑐+𝑑)");
+        assert_eq!(unicode("x^{-1.5}"), "𝑥^(−1.5)");
+        assert_eq!(unicode(r"x_{\frac{1}{2}}"), "𝑥_½");
+    }
+}
@@ -0,0 +1,42 @@
+use std::fmt;
+
+use crate::element::ParseError;
+
+/// Everything that can go wrong between LaTeX or JSON input and finished output
+#[derive(Debug)]
+pub enum Error {
+    /// The LaTeX source could not be parsed. `ParseError::render` shows it with the source.
+    Parse(ParseError),
+    /// A JSON document could not be read or written
+    Json(String),
+    /// A settings value is out of range
+    InvalidSettings(String),
+    /// The formula could not be laid out or drawn, for example because it is too large
+    Render(String),
+    /// An output format could not be produced
+    Output(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(error) => write!(f, "{}", error),
+            Error::Json(message) | Error::InvalidSettings(message) | Error::Render(message) | Error::Output(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::Parse(error)
+    }
+}
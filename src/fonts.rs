@@ -1,12 +1,30 @@
 use fontdue::Font;
 use lazy_static::lazy_static;
+use ttf_parser::Face;
 
-pub static KaTeX_Main_Regular: &'static [u8] = include_bytes!("../fonts/KaTeX_Main-Regular.ttf");
-pub static KaTeX_Main_Italic: &'static [u8] = include_bytes!("../fonts/KaTeX_Main-Italic.ttf");
+pub static KaTeX_Main_Regular: &[u8] = include_bytes!("../fonts/KaTeX_Main-Regular.ttf");
+pub static KaTeX_Main_Italic: &[u8] = include_bytes!("../fonts/KaTeX_Main-Italic.ttf");
+
+/// Raw font files, indexed like `FONTS` and `FACES`
+pub static FONT_DATA: [&[u8]; 2] = [KaTeX_Main_Regular, KaTeX_Main_Italic];
 
 lazy_static! {
-    pub static ref FONTS: Vec<Font> = vec![
-        Font::from_bytes(KaTeX_Main_Regular, fontdue::FontSettings::default()).unwrap(),
-        Font::from_bytes(KaTeX_Main_Italic, fontdue::FontSettings::default()).unwrap(),
-    ];
-}
\ No newline at end of file
+    pub static ref FONTS: Vec<Font> = FONT_DATA.iter()
+        .map(|data| Font::from_bytes(*data, fontdue::FontSettings::default()).unwrap())
+        .collect();
+
+    /// Outline access to the same fonts as `FONTS` (fontdue only exposes raster data), indexed identically
+    pub static ref FACES: Vec<Face<'static>> = FONT_DATA.iter()
+        .map(|data| Face::parse(data, 0).unwrap())
+        .collect();
+
+    /// Height of the math axis above the baseline in ems, taken from the center of the minus sign
+    pub static ref AXIS_HEIGHT: f32 = {
+        let face = &FACES[0];
+        let bounds = face.glyph_index('\u{2212}')
+            .and_then(|glyph| face.glyph_bounding_box(glyph))
+            .expect("the regular font has a minus sign");
+
+        (bounds.y_min as f32 + bounds.y_max as f32) / 2. / face.units_per_em() as f32
+    };
+}
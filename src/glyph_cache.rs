@@ -0,0 +1,227 @@
+use std::{fmt, num::NonZeroUsize, sync::{Arc, Mutex, MutexGuard, PoisonError}};
+
+use lru::LruCache;
+
+use crate::fonts::FONTS;
+
+/// Glyphs are rasterized at fractional offsets rounded to this many steps per pixel
+const SUBPIXEL_STEPS: f32 = 4.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font_index: usize,
+    glyph_index: u16,
+    /// Bits of the pixel size, so sizes compare exactly
+    px: u32,
+    /// Fractional offset in steps of `1/SUBPIXEL_STEPS` pixels
+    subpixel: (u8, u8),
+}
+
+/// Coverage of a rasterized glyph, one byte per pixel
+pub(crate) struct GlyphRaster {
+    pub width: usize,
+    pub height: usize,
+    pub coverage: Vec<u8>,
+}
+
+/// Rasterized glyph coverage, kept across renders. When full, the least recently used glyph is evicted.
+///
+/// The cache can be shared between threads. Glyphs are rasterized without holding the lock, so
+/// concurrent renders only wait on each other for lookups.
+pub(crate) struct GlyphCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+struct CacheState {
+    /// Kept in order of use, so finding the least recently used glyph takes constant time
+    entries: LruCache<GlyphKey, Arc<GlyphRaster>>,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+/// Counters of a `GlyphCache` since it was created
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+impl GlyphCache {
+    /// Creates a cache holding at most `capacity` glyphs. A capacity of 0 disables caching.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(CacheState {
+                // Nothing is inserted when the capacity is 0
+                entries: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+                hits: 0,
+                misses: 0,
+                evictions: 0,
+            }),
+        }
+    }
+
+    /// Returns a glyph rasterized `dx` and `dy` pixels (0.0 - 1.0) right of and below its usual
+    /// position, rasterizing it on a miss
+    pub fn glyph(&self, font_index: usize, glyph_index: u16, px: f32, dx: f32, dy: f32) -> Arc<GlyphRaster> {
+        let subpixel = ((dx * SUBPIXEL_STEPS).round() as u8, (dy * SUBPIXEL_STEPS).round() as u8);
+        let key = GlyphKey { font_index, glyph_index, px: px.to_bits(), subpixel };
+
+        {
+            let mut state = self.lock();
+            if let Some(raster) = state.entries.get(&key).cloned() {
+                state.hits += 1;
+                return raster;
+            }
+            state.misses += 1;
+        }
+
+        // fontdue only rasterizes glyphs at its own origin, its offset parameter is private. So the
+        // glyph is rasterized there and its coverage shifted, which softens edges slightly. That is
+        // still much closer than snapping glyphs to whole pixels, and a real offset would mean
+        // rasterizing the outlines ourselves.
+        let (metrics, coverage) = FONTS[font_index].rasterize_indexed(glyph_index, px);
+        let raster = GlyphRaster { width: metrics.width, height: metrics.height, coverage }
+            .shifted(subpixel.0 as f32 / SUBPIXEL_STEPS, subpixel.1 as f32 / SUBPIXEL_STEPS);
+        let raster = Arc::new(raster);
+
+        if self.capacity == 0 {
+            return raster;
+        }
+
+        let mut state = self.lock();
+        // Another thread may have rasterized the same glyph in the meantime
+        if let Some(cached) = state.entries.get(&key) {
+            return cached.clone();
+        }
+        // The key isn't cached, so anything pushed out is the least recently used glyph
+        if state.entries.push(key, raster.clone()).is_some() {
+            state.evictions += 1;
+        }
+
+        raster
+    }
+
+    pub fn stats(&self) -> GlyphCacheStats {
+        let state = self.lock();
+        GlyphCacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            evictions: state.evictions,
+            entries: state.entries.len(),
+            capacity: self.capacity,
+        }
+    }
+
+    /// The cache holds no invariants a panicking render could break, so a poisoned lock is still usable
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl GlyphRaster {
+    /// Moves the coverage by a fraction of a pixel, spreading every pixel over the (up to) four
+    /// pixels it now overlaps. The raster grows by a pixel in each direction it moves.
+    ///
+    /// This approximates rasterizing at the offset: the total coverage is kept, but edges come
+    /// out slightly softer.
+    fn shifted(self, dx: f32, dy: f32) -> Self {
+        if dx == 0. && dy == 0. {
+            return self;
+        }
+
+        let width = self.width + (dx > 0.) as usize;
+        let height = self.height + (dy > 0.) as usize;
+        let mut coverage = vec![0f32; width * height];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let value = self.coverage[y * self.width + x] as f32;
+                if value == 0. {
+                    continue;
+                }
+
+                coverage[y * width + x] += value * (1. - dx) * (1. - dy);
+                if dx > 0. {
+                    coverage[y * width + x + 1] += value * dx * (1. - dy);
+                }
+                if dy > 0. {
+                    coverage[(y + 1) * width + x] += value * (1. - dx) * dy;
+                }
+                if dx > 0. && dy > 0. {
+                    coverage[(y + 1) * width + x + 1] += value * dx * dy;
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            coverage: coverage.into_iter().map(|c| c.round().min(255.) as u8).collect(),
+        }
+    }
+}
+
+impl GlyphCacheStats {
+    /// Fraction of lookups that were served from the cache, 0 when there were none
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+impl fmt::Display for GlyphCacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.1}% hit rate), {} evictions, {}/{} glyphs cached",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.,
+            self.evictions,
+            self.entries,
+            self.capacity
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_the_least_recently_used_glyph() {
+        let cache = GlyphCache::new(2);
+        let glyph = |index| cache.glyph(0, index, 12., 0., 0.);
+
+        glyph(1);
+        glyph(2);
+        glyph(1);
+        // Glyph 2 was used least recently
+        glyph(3);
+        glyph(1);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions, stats.entries), (2, 3, 1, 2));
+
+        glyph(2);
+        assert_eq!(cache.stats().misses, 4);
+    }
+
+    #[test]
+    fn a_capacity_of_zero_caches_nothing() {
+        let cache = GlyphCache::new(0);
+        cache.glyph(0, 1, 12., 0., 0.);
+        cache.glyph(0, 1, 12., 0., 0.);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (0, 2, 0));
+    }
+}
@@ -0,0 +1,98 @@
+use fontdue::layout::GlyphPosition;
+
+use crate::{bitmap::{BlendMode, Color}, canvas::Canvas, glyph_cache::GlyphCache};
+
+/// A laid out element. Boxes are measured and positioned once, then drawn by any `Canvas`.
+///
+/// Siblings are lined up on their baselines: `height` is the extent above the baseline and
+/// `depth` the extent below it. All sizes are in fractional pixels.
+#[derive(Debug, Clone)]
+pub struct LayoutBox {
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+    pub(crate) depth: f32,
+    pub(crate) content: LayoutContent,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum LayoutContent {
+    Items(Vec<LayoutItem>),
+    /// A run of glyphs positioned by a fontdue layout, shifted right by `xoffset`
+    Glyphs {
+        glyphs: Vec<GlyphPosition>,
+        /// Baseline of the line the glyphs were laid out on
+        line_baseline: f32,
+        xoffset: f32,
+        color: Color,
+    },
+}
+
+/// Content of a box, positioned relative to its top left corner
+#[derive(Debug, Clone)]
+pub(crate) enum LayoutItem {
+    Box {
+        x: f32,
+        y: f32,
+        node: LayoutBox,
+    },
+    Rule {
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+        thickness: f32,
+        color: Color,
+    },
+}
+
+impl LayoutBox {
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Extent above the baseline
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// Extent below the baseline
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+
+    pub fn total_height(&self) -> f32 {
+        self.height + self.depth
+    }
+
+    /// Draws the box onto a new canvas of type `C` just large enough to hold it, taking rasterized glyphs
+    /// from `cache` and combining overlapping ink with `blend_mode`
+    pub(crate) fn render<C: Canvas>(&self, cache: &GlyphCache, blend_mode: BlendMode) -> C {
+        let mut canvas = C::new(self.width.ceil() as usize, self.total_height().ceil() as usize);
+        canvas.set_blend_mode(blend_mode);
+        self.draw(&mut canvas, 0., 0., cache);
+        canvas.set_baseline(self.height);
+        canvas
+    }
+
+    /// Draws the box and everything in it with its top left corner at (`x`, `y`). Offsets are
+    /// accumulated without rounding, so only the canvas decides how to snap them to pixels.
+    fn draw<C: Canvas>(&self, canvas: &mut C, x: f32, y: f32, cache: &GlyphCache) {
+        match &self.content {
+            LayoutContent::Items(items) => {
+                for item in items {
+                    match item {
+                        LayoutItem::Box { x: dx, y: dy, node } => node.draw(canvas, x + dx, y + dy, cache),
+                        LayoutItem::Rule { x0, y0, x1, y1, thickness, color } => {
+                            canvas.draw_line(x + x0, y + y0, x + x1, y + y1, *thickness, *color)
+                        }
+                    }
+                }
+            }
+            LayoutContent::Glyphs { glyphs, line_baseline, xoffset, color } => {
+                for glyph in glyphs {
+                    canvas.draw_glyph(glyph, x + xoffset, y, *line_baseline, *color, cache);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Renders LaTeX math to bitmaps, terminal graphics, SVG and PDF.
+//!
+//! Formulas are parsed into a [`KElement`] tree with [`parse`], [`parse_lenient`] or [`from_json`],
+//! then drawn by a [`RusTeX`] renderer configured with [`TeXSettings`]. The tree can also be written
+//! back out as text with `KElement::to_unicode`, `to_pretty`, `to_mathml`, `to_json` or `Display`.
+//! Many formulas can be rendered to files in parallel with `RusTeX::render_batch`.
+
+#[allow(non_upper_case_globals)]
+mod fonts;
+mod batch;
+mod element;
+mod bitmap;
+mod canvas;
+mod consts;
+mod error;
+mod glyph_cache;
+mod layout;
+mod output;
+mod renderer;
+mod settings;
+
+pub use crate::{
+    batch::{BatchFormat, BatchItem},
+    bitmap::{Bitmap, BlendMode, Color},
+    element::{KElement, MathStyle, ParseError, ParseErrorKind, Span},
+    error::Error,
+    glyph_cache::GlyphCacheStats,
+    layout::LayoutBox,
+    output::{
+        blocks::{BlockMode, BlockOptions, Dithering},
+        iterm::ItermOptions,
+        kitty::{kitty_delete, kitty_place, KittyFormat, KittyOptions},
+        pdf::PdfOptions,
+        png::{PngColorType, PngOptions, SOURCE_KEYWORD},
+    },
+    renderer::RusTeX,
+    settings::{TeXSettings, TeXSettingsBuilder},
+};
+
+/// Parses a LaTeX formula, stopping at the first error
+pub fn parse(latex: &str) -> Result<KElement, ParseError> {
+    KElement::parse(latex)
+}
+
+/// Parses a LaTeX formula, keeping whatever failed to parse as `KElement::Error` so it can still be
+/// rendered. Every problem found is returned alongside the tree.
+pub fn parse_lenient(latex: &str) -> (KElement, Vec<ParseError>) {
+    KElement::parse_lenient(latex)
+}
+
+/// Reads a `rustex-element` or `rustex-parsed` JSON document
+pub fn from_json(json: &str) -> Result<KElement, Error> {
+    KElement::from_json(json)
+}
+
+// Renderers and parsed formulas are shared between threads, keep it that way
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<RusTeX>();
+    assert_send_sync::<KElement>();
+    assert_send_sync::<TeXSettings>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_shared_renderer_draws_the_same_as_a_single_thread() {
+        let formulas = [r"\frac{a+b}{2}", "x^2+y_1", r"1.5\pm 0.25", r"\displaystyle\frac{x}{y}=z", "(a+b)^2", "k_{n+1}"];
+        let elements: Vec<KElement> = formulas.iter().map(|formula| parse(formula).unwrap()).collect();
+
+        let expected: Vec<Bitmap> = elements.iter()
+            .map(|element| RusTeX::new(TeXSettings::default()).rasterize(element).unwrap())
+            .collect();
+
+        let shared = RusTeX::new(TeXSettings::default());
+        std::thread::scope(|scope| {
+            for thread in 0..elements.len() {
+                let (shared, elements, expected) = (&shared, &elements, &expected);
+                scope.spawn(move || {
+                    // Threads start at different formulas and overlap, so they race on the same glyphs
+                    for round in 0..3 {
+                        let index = (thread + round) % elements.len();
+                        let bitmap = shared.rasterize(&elements[index]).unwrap();
+                        assert_eq!((bitmap.width, bitmap.height), (expected[index].width, expected[index].height));
+                        assert!(bitmap.data == expected[index].data, "{} differs when rendered in parallel", formulas[index]);
+                    }
+                });
+            }
+        });
+
+        assert!(shared.glyph_cache_stats().hits > 0);
+    }
+}
@@ -1,79 +1,456 @@
-#[allow(non_upper_case_globals)] 
+use std::{io::Read, path::{Path, PathBuf}, process::ExitCode, str::FromStr, time::Instant};
 
-mod fonts;
-mod element;
-mod bitmap;
-mod consts;
+use rustex::{kitty_delete, kitty_place, BatchFormat, BatchItem, BlendMode, BlockMode, BlockOptions, Color, Dithering, Error, ItermOptions, KElement, KittyFormat, KittyOptions, PdfOptions, PngColorType, PngOptions, RusTeX, TeXSettings, TeXSettingsBuilder};
 
-use std::time::Instant;
-
-use fontdue::{layout::{CoordinateSystem, Layout, LayoutSettings}};
+fn main() -> ExitCode {
+    parse_test()
+}
 
-use crate::{bitmap::Bitmap, element::{KElement}};
+enum OutputMode {
+    /// Print to the terminal as sixel
+    Sixel,
+    Png(String),
+    Svg(String),
+    Pdf(String),
+    /// Print to the terminal with the kitty graphics protocol
+    Kitty,
+    /// Print to the terminal with the iTerm2 inline image protocol
+    Iterm,
+    /// Print to the terminal as Unicode block or braille characters
+    Blocks,
+    /// Print as a single line of Unicode text
+    Unicode,
+    /// Print as multi-line text art, with box drawing characters unless ASCII is requested
+    Pretty,
+    /// Print as presentation MathML
+    MathMl,
+    /// Print as normalized LaTeX
+    Latex,
+    /// Print the element tree as JSON
+    Json,
+    /// Print the parser objects as JSON
+    ParsedJson,
+}
 
-fn main() -> Result<(), std::fmt::Error> {
-    parse_test()
+/// Acts on an image already transmitted to a kitty terminal, instead of rendering a formula
+enum KittyCommand {
+    /// Display the image again at the cursor
+    Place(u32),
+    /// Delete the image and its placements
+    Delete(u32),
 }
 
-fn parse_test() -> Result<(), std::fmt::Error> {    
-    let tex_input = &std::env::args().nth(1).unwrap();
+fn parse_test() -> ExitCode {    
+    let mut tex_inputs = Vec::new();
+    let mut settings = TeXSettings::builder();
+    let mut output = OutputMode::Sixel;
+    let mut png_options = PngOptions::default();
+    let mut pdf_options = PdfOptions::default();
+    let mut kitty_options = KittyOptions::default();
+    let mut kitty_command = None;
+    let mut iterm_options = ItermOptions::default();
+    let (mut columns, mut rows) = (None, None);
+    let mut block_options = BlockOptions::default();
+    let mut background_given = false;
+    let mut ascii = false;
+    let mut inline = false;
+    let mut from_json = false;
+    let mut lenient = false;
+    let mut cache_stats = false;
+    let mut batch = None;
+    let mut batch_format = "png".to_string();
+    let mut out_dir = PathBuf::from(".");
+    let mut jobs = None;
 
-    let mut start = Instant::now();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let result = match arg.as_str() {
+            "--scale" => parse_number_arg(&mut args, &arg).map(|scale| { settings.scale(scale); }),
+            "--fg" => parse_color_arg(&mut args, &arg).map(|c| { settings.foreground(c); }),
+            "--error-color" => parse_color_arg(&mut args, &arg).map(|c| { settings.error_color(c); }),
+            "--bg" => parse_color_arg(&mut args, &arg).map(|c| {
+                settings.background(c);
+                background_given = true;
+            }),
+            "--transparent" => {
+                settings.background(Color::TRANSPARENT);
+                background_given = true;
+                Ok(())
+            }
+            "--png" => next_arg(&mut args, &arg).map(|path| output = OutputMode::Png(path)),
+            "--svg" => next_arg(&mut args, &arg).map(|path| output = OutputMode::Svg(path)),
+            "--pdf" => next_arg(&mut args, &arg).map(|path| output = OutputMode::Pdf(path)),
+            "--dpi" => parse_number_arg(&mut args, &arg).map(|dpi| png_options.dpi = Some(dpi)),
+            "--gray" => {
+                png_options.color_type = PngColorType::GrayscaleAlpha;
+                Ok(())
+            }
+            "--font-size" => parse_number_arg(&mut args, &arg).map(|size| pdf_options.font_size = size),
+            "--margin" => parse_number_arg(&mut args, &arg).map(|margin| pdf_options.margin = margin),
+            "--kitty" => {
+                output = OutputMode::Kitty;
+                Ok(())
+            }
+            "--kitty-rgba" => {
+                kitty_options.format = KittyFormat::Rgba;
+                Ok(())
+            }
+            "--image-id" => parse_number_arg(&mut args, &arg).map(|id| kitty_options.image_id = Some(id)),
+            "--cols" => parse_number_arg(&mut args, &arg).map(|cols| columns = Some(cols)),
+            "--rows" => parse_number_arg(&mut args, &arg).map(|n| rows = Some(n)),
+            "--kitty-place" => parse_number_arg(&mut args, &arg).map(|id| kitty_command = Some(KittyCommand::Place(id))),
+            "--kitty-delete" => parse_number_arg(&mut args, &arg).map(|id| kitty_command = Some(KittyCommand::Delete(id))),
+            "--iterm" => {
+                output = OutputMode::Iterm;
+                Ok(())
+            }
+            "--stretch" => {
+                iterm_options.preserve_aspect_ratio = false;
+                Ok(())
+            }
+            "--blocks" => next_arg(&mut args, &arg).and_then(|mode| {
+                block_options.mode = match mode.as_str() {
+                    "half" => BlockMode::HalfBlock,
+                    "quad" => BlockMode::Quadrant,
+                    "braille" => BlockMode::Braille,
+                    _ => return Err(format!("Invalid block mode: '{}', expected half, quad or braille", mode)),
+                };
+                output = OutputMode::Blocks;
+                Ok(())
+            }),
+            "--dither" => {
+                block_options.dithering = Dithering::Ordered;
+                Ok(())
+            }
+            "--ansi-color" => {
+                block_options.color = true;
+                Ok(())
+            }
+            "--unicode" => {
+                output = OutputMode::Unicode;
+                Ok(())
+            }
+            "--pretty" => {
+                output = OutputMode::Pretty;
+                Ok(())
+            }
+            "--ascii" => {
+                ascii = true;
+                Ok(())
+            }
+            "--mathml" => {
+                output = OutputMode::MathMl;
+                Ok(())
+            }
+            "--latex" => {
+                output = OutputMode::Latex;
+                Ok(())
+            }
+            "--json" => {
+                output = OutputMode::Json;
+                Ok(())
+            }
+            "--parsed-json" => {
+                output = OutputMode::ParsedJson;
+                Ok(())
+            }
+            "--from-json" => {
+                from_json = true;
+                Ok(())
+            }
+            "--lenient" => {
+                lenient = true;
+                Ok(())
+            }
+            "--inline" => {
+                inline = true;
+                settings.display(false);
+                Ok(())
+            }
+            "--blend" => next_arg(&mut args, &arg).and_then(|mode| {
+                settings.blend_mode(match mode.as_str() {
+                    "replace" => BlendMode::Replace,
+                    "max" => BlendMode::Max,
+                    "over" => BlendMode::SourceOver,
+                    "multiply" => BlendMode::Multiply,
+                    _ => return Err(format!("Invalid blend mode: '{}', expected replace, max, over or multiply", mode)),
+                });
+                Ok(())
+            }),
+            "--glyph-cache" => parse_number_arg(&mut args, &arg).map(|capacity| { settings.glyph_cache_capacity(capacity); }),
+            "--cache-stats" => {
+                cache_stats = true;
+                Ok(())
+            }
+            "--batch" => next_arg(&mut args, &arg).map(|manifest| batch = Some(manifest)),
+            "--format" => next_arg(&mut args, &arg).and_then(|format| match format.as_str() {
+                "png" | "svg" | "pdf" => {
+                    batch_format = format;
+                    Ok(())
+                }
+                _ => Err(format!("Invalid format: '{}', expected png, svg or pdf", format)),
+            }),
+            "--out-dir" => next_arg(&mut args, &arg).map(|dir| out_dir = PathBuf::from(dir)),
+            "--jobs" => parse_number_arg(&mut args, &arg).map(|n: usize| jobs = Some(n)),
+            _ => {
+                tex_inputs.push(arg);
+                Ok(())
+            }
+        };
+
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
 
-    match KElement::parse(tex_input) {
-        Ok(result) => {
-            println!("Parse time: {:?}", start.elapsed());
-            
-            start = Instant::now();
-            let mut rustex = RusTeX::new(TeXSettings { scale: 100. });
+    // Placing and deleting work on images already in the terminal and need no formula
+    if let Some(command) = kitty_command {
+        match command {
+            KittyCommand::Place(id) => print!("{}", kitty_place(id, columns, rows, kitty_options.quiet)),
+            KittyCommand::Delete(id) => print!("{}", kitty_delete(id, kitty_options.quiet)),
+        }
+        return ExitCode::SUCCESS;
+    }
 
-            let bitmap = rustex.rasterize(result);
-            
-            println!("Raster time: {:?}", start.elapsed());
-            start = Instant::now();
+    if let Some(manifest) = batch {
+        let format = match batch_format.as_str() {
+            "svg" => BatchFormat::Svg,
+            "pdf" => BatchFormat::Pdf(pdf_options),
+            _ => BatchFormat::Png(png_options),
+        };
 
-            bitmap.print();
+        return match run_batch(&manifest, format, &out_dir, jobs, settings, cache_stats) {
+            Ok(0) => ExitCode::SUCCESS,
+            // Each failure was already reported
+            Ok(_) => ExitCode::FAILURE,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if tex_inputs.is_empty() {
+        eprintln!("Usage: rustex [--scale <px>] [--inline] [--fg <color>] [--bg <color>] [--lenient [--error-color <color>]] [--transparent] [--blend <replace|max|over|multiply>] [--glyph-cache <n>] [--cache-stats] [--png <file> [--dpi <dpi>] [--gray]] [--svg <file>] [--pdf <file> [--font-size <pt>] [--margin <pt>]] [--kitty [--kitty-rgba] [--image-id <id>]] [--iterm [--stretch]] [--cols <n>] [--rows <n>] [--blocks <half|quad|braille> [--dither] [--ansi-color]] [--unicode] [--pretty [--ascii]] [--mathml] [--latex] [--json] [--parsed-json] [--from-json] <latex | json file>...");
+        eprintln!("       rustex [options] --batch <manifest | -> [--format <png|svg|pdf>] [--out-dir <dir>] [--jobs <n>]");
+        eprintln!("       rustex [--cols <n>] [--rows <n>] --kitty-place <id> | --kitty-delete <id>");
+        return ExitCode::FAILURE;
+    }
 
-            println!("Display time: {:?}", start.elapsed());
+    if tex_inputs.len() > 1 && matches!(output, OutputMode::Png(_) | OutputMode::Svg(_)) {
+        eprintln!("Error: Only PDF and terminal output accept several formulas");
+        return ExitCode::FAILURE;
+    }
+
+    if from_json && matches!(output, OutputMode::ParsedJson) {
+        eprintln!("Error: Parser objects can only be printed for LaTeX input");
+        return ExitCode::FAILURE;
+    }
+
+    let mut start = Instant::now();
+
+    let mut elements = Vec::new();
+    for tex_input in &tex_inputs {
+        // Parse errors come with their own header and source excerpt
+        let parsed = if from_json {
+            std::fs::read_to_string(tex_input)
+                .map_err(|e| format!("{}: {}", tex_input, e))
+                .and_then(|json| rustex::from_json(&json).map_err(|e| e.to_string()))
+                .map_err(|e| format!("Error: {}", e))
+        } else if lenient {
+            // Problems are reported, but the formula is still rendered with them shown inline
+            let (element, errors) = rustex::parse_lenient(tex_input);
+            for error in errors {
+                eprintln!("{}", error.render(tex_input));
+            }
+            Ok(element)
+        } else {
+            rustex::parse(tex_input).map_err(|e| e.render(tex_input))
+        };
+
+        match parsed {
+            Ok(result) => elements.push(result),
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
         }
-        Err(e) => println!("Error: {}", e),
     }
 
-    
-    Ok(())
-    
-}
+    eprintln!("Parse time: {:?}", start.elapsed());
+    start = Instant::now();
 
+    // Block characters take coverage from the alpha channel, only colored half blocks can show a background
+    if matches!(output, OutputMode::Blocks) && !(block_options.color && background_given) {
+        settings.background(Color::TRANSPARENT);
+    }
 
-struct RusTeX {
-    pub settings: TeXSettings,
-    pub layout: Layout
-}
+    let settings = match settings.build() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let rustex = RusTeX::new(settings);
+
+    (kitty_options.columns, kitty_options.rows) = (columns, rows);
+    (iterm_options.columns, iterm_options.rows) = (columns, rows);
+
+    let result = match output {
+        OutputMode::Sixel => elements.iter()
+            .try_for_each(|element| rustex.rasterize(element)?.print()),
+        OutputMode::Kitty => elements.iter()
+            .try_for_each(|element| rustex.rasterize(element)?.print_kitty(&kitty_options)),
+        OutputMode::Iterm => elements.iter()
+            .try_for_each(|element| rustex.rasterize(element)?.print_iterm(&iterm_options)),
+        OutputMode::Blocks => elements.iter().try_for_each(|element| {
+            print!("{}", rustex.rasterize(element)?.to_blocks(&block_options));
+            Ok(())
+        }),
+        OutputMode::Unicode => {
+            for element in elements {
+                println!("{}", element.to_unicode());
+            }
+            Ok(())
+        }
+        OutputMode::Pretty => {
+            for element in elements {
+                println!("{}", element.to_pretty(!ascii));
+            }
+            Ok(())
+        }
+        OutputMode::MathMl => {
+            for element in elements {
+                println!("{}", element.to_mathml(!inline));
+            }
+            Ok(())
+        }
+        OutputMode::Latex => {
+            for element in elements {
+                println!("{}", element);
+            }
+            Ok(())
+        }
+        OutputMode::Json => elements.iter().try_for_each(|element| {
+            println!("{}", element.to_json()?);
+            Ok(())
+        }),
+        OutputMode::ParsedJson => tex_inputs.iter().try_for_each(|tex_input| {
+            println!("{}", KElement::parse_to_json(tex_input)?);
+            Ok(())
+        }),
+        OutputMode::Png(path) => {
+            png_options.source = Some(tex_inputs[0].clone());
+            let element = elements.pop().unwrap();
+            rustex.rasterize(&element).and_then(|bitmap| bitmap.save_png(&path, &png_options))
+        }
+        OutputMode::Svg(path) => {
+            let element = elements.pop().unwrap();
+            rustex.render_svg(&element).and_then(|svg| write_output(&path, svg))
+        }
+        OutputMode::Pdf(path) => {
+            rustex.render_pdf(&elements, pdf_options).and_then(|pdf| write_output(&path, pdf))
+        }
+    };
 
-struct TeXSettings {
-    scale: f32,
+    let code = match result {
+        Ok(()) => {
+            eprintln!("Render time: {:?}", start.elapsed());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    };
+
+    if cache_stats {
+        eprintln!("Glyph cache: {}", rustex.glyph_cache_stats());
+    }
+
+    code
 }
 
-impl RusTeX {
-    pub fn new(settings: TeXSettings) -> Self {
-        let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
+/// Renders every formula of a manifest to its own file, in parallel.
+///
+/// Each non-empty line of the manifest that doesn't start with `#` is either `<output file><TAB><latex>`
+/// or just `<latex>`, which is written to `<out_dir>/<line number>.<format>`. A manifest of `-` is read
+/// from standard input. Formulas that fail are reported with their line and don't stop the others.
+/// Returns how many failed.
+fn run_batch(manifest: &str, format: BatchFormat, out_dir: &Path, jobs: Option<usize>, settings: TeXSettingsBuilder, cache_stats: bool) -> Result<usize, String> {
+    let mut text = String::new();
+    if manifest == "-" {
+        std::io::stdin().read_to_string(&mut text).map_err(|e| format!("stdin: {}", e))?;
+    } else {
+        text = std::fs::read_to_string(manifest).map_err(|e| format!("{}: {}", manifest, e))?;
+    }
 
-        layout.reset(&LayoutSettings {
-            ..LayoutSettings::default()
-        });
+    // Without --jobs, rayon's global pool uses one thread per core
+    let pool = jobs
+        .map(|jobs| rayon::ThreadPoolBuilder::new().num_threads(jobs).build().map_err(|e| e.to_string()))
+        .transpose()?;
+    std::fs::create_dir_all(out_dir).map_err(|e| format!("{}: {}", out_dir.display(), e))?;
 
-        Self {
-            settings,
-            layout,
+    let (mut line_numbers, mut items) = (Vec::new(), Vec::new());
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
         }
+
+        let (output, source) = match line.split_once('\t') {
+            Some((output, source)) => (PathBuf::from(output), source),
+            None => (out_dir.join(format!("{}.{}", i + 1, format.extension())), line),
+        };
+        line_numbers.push(i + 1);
+        items.push(BatchItem { source: source.to_string(), output });
     }
 
-    // pub fn add_text_style(&mut self, text_style: &TextStyle) {
-    //     // self.layout.append(&fonts::FONTS, text_style);
-    // }
+    let rustex = RusTeX::new(settings.build().map_err(|e| e.to_string())?);
+    let start = Instant::now();
+    let results = match &pool {
+        Some(pool) => pool.install(|| rustex.render_batch(&items, &format)),
+        None => rustex.render_batch(&items, &format),
+    };
 
-    pub fn rasterize(&mut self, root_element: KElement) -> Bitmap {
-        let scale = self.settings.scale;
-        root_element.rasterize(self, scale)
+    let mut failed = 0;
+    for ((item, line_number), result) in items.iter().zip(line_numbers).zip(results) {
+        match result {
+            Ok(()) => {}
+            // Parse errors come with their own header and source excerpt
+            Err(Error::Parse(e)) => {
+                failed += 1;
+                eprintln!("{}:{}:\n{}", manifest, line_number, e.render(&item.source));
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}:{}: Error: {}", manifest, line_number, e);
+            }
+        }
+    }
+
+    eprintln!("Rendered {} of {} formulas in {:?}, {} failed", items.len() - failed, items.len(), start.elapsed(), failed);
+    if cache_stats {
+        eprintln!("Glyph cache: {}", rustex.glyph_cache_stats());
     }
-}
\ No newline at end of file
+
+    Ok(failed)
+}
+
+fn write_output(path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> Result<(), Error> {
+    let path = path.as_ref();
+    std::fs::write(path, data).map_err(|e| Error::Output(format!("{}: {}", path.display(), e)))
+}
+
+fn next_arg(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("Expected a value after {}", flag))
+}
+
+fn parse_color_arg(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<Color, String> {
+    next_arg(args, flag).and_then(|value| Color::from_hex(&value).map_err(|e| e.to_string()))
+}
+
+fn parse_number_arg<T: FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<T, String> {
+    next_arg(args, flag).and_then(|value| value.parse::<T>().map_err(|_| format!("Invalid number for {}: '{}'", flag, value)))
+}
@@ -0,0 +1,219 @@
+use std::fmt::Write;
+
+use crate::bitmap::{Bitmap, Color};
+
+/// Quadrant characters indexed by their filled quarters (top left = 1, top right = 2, bottom left = 4, bottom right = 8)
+const QUADRANTS: [char; 16] = [' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█'];
+
+/// Braille dot bits indexed by `[y][x]` within a 2x4 cell
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// 4x4 Bayer matrix for ordered dithering
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockMode {
+    /// `▀`/`▄`/`█`, 1x2 pixels per cell
+    HalfBlock,
+    /// `▘▝▖▗` and combinations, 2x2 pixels per cell
+    Quadrant,
+    /// Braille patterns, 2x4 pixels per cell
+    Braille,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dithering {
+    /// A pixel is set when its coverage reaches the threshold (0 - 255)
+    Threshold(u8),
+    /// Ordered dithering, which keeps thin antialiased strokes visible
+    Ordered,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockOptions {
+    pub mode: BlockMode,
+    pub dithering: Dithering,
+    /// Colors cells with 24-bit ANSI escape sequences
+    pub color: bool,
+}
+
+impl Default for BlockOptions {
+    fn default() -> Self {
+        Self {
+            mode: BlockMode::HalfBlock,
+            dithering: Dithering::Threshold(128),
+            color: false,
+        }
+    }
+}
+
+impl Bitmap {
+    /// Renders the bitmap as Unicode block or braille characters, one line of text per row of cells.
+    ///
+    /// Coverage is read from the alpha channel, so the bitmap should be rendered with a transparent background.
+    pub fn to_blocks(&self, options: &BlockOptions) -> String {
+        let (cell_width, cell_height) = match options.mode {
+            BlockMode::HalfBlock => (1, 2),
+            BlockMode::Quadrant => (2, 2),
+            BlockMode::Braille => (2, 4),
+        };
+
+        let mut output = String::new();
+
+        for cell_y in (0..self.height).step_by(cell_height) {
+            for cell_x in (0..self.width).step_by(cell_width) {
+                if options.color && options.mode == BlockMode::HalfBlock {
+                    // Both halves get their own color, so no thresholding is needed
+                    let top = self.pixel_or_transparent(cell_x, cell_y);
+                    let bottom = self.pixel_or_transparent(cell_x, cell_y + 1);
+                    push_half_block(&mut output, top, bottom);
+                    continue;
+                }
+
+                let mut bits = 0;
+                let (mut r, mut g, mut b, mut total) = (0., 0., 0., 0.);
+
+                // The braille cell is the largest, so its dot table also drives the smaller cells
+                for (dy, braille_row) in BRAILLE_DOTS.iter().enumerate().take(cell_height) {
+                    for (dx, braille_dot) in braille_row.iter().enumerate().take(cell_width) {
+                        let (x, y) = (cell_x + dx, cell_y + dy);
+                        let pixel = self.pixel_or_transparent(x, y);
+                        if !is_set(pixel.a, x, y, options.dithering) {
+                            continue;
+                        }
+
+                        bits |= match options.mode {
+                            BlockMode::HalfBlock => 1 << dy,
+                            BlockMode::Quadrant => 1 << (dy*2 + dx),
+                            BlockMode::Braille => *braille_dot,
+                        };
+
+                        let weight = pixel.a as f32;
+                        r += pixel.r as f32 * weight;
+                        g += pixel.g as f32 * weight;
+                        b += pixel.b as f32 * weight;
+                        total += weight;
+                    }
+                }
+
+                let character = match options.mode {
+                    BlockMode::HalfBlock => [' ', '▀', '▄', '█'][bits as usize],
+                    BlockMode::Quadrant => QUADRANTS[bits as usize],
+                    BlockMode::Braille => char::from_u32(0x2800 + bits).unwrap(),
+                };
+
+                if options.color && total > 0. {
+                    let _ = write!(
+                        output, "\x1b[38;2;{};{};{}m{}",
+                        (r / total).round(), (g / total).round(), (b / total).round(), character
+                    );
+                } else {
+                    if options.color {
+                        output.push_str("\x1b[39m");
+                    }
+                    output.push(character);
+                }
+            }
+
+            if options.color {
+                output.push_str("\x1b[0m");
+            }
+            output.push('\n');
+        }
+
+        output
+    }
+
+    fn pixel_or_transparent(&self, x: usize, y: usize) -> Color {
+        if x < self.width && y < self.height {
+            self.data[y*self.width + x]
+        } else {
+            Color::TRANSPARENT
+        }
+    }
+}
+
+fn is_set(coverage: u8, x: usize, y: usize, dithering: Dithering) -> bool {
+    match dithering {
+        Dithering::Threshold(threshold) => coverage >= threshold.max(1),
+        Dithering::Ordered => coverage as u32 * 16 > (BAYER[y % 4][x % 4] as u32 * 2 + 1) * 255 / 2,
+    }
+}
+
+/// Writes an upper half block whose top and bottom halves take the two pixel colors.
+/// Transparent pixels use the terminal's default colors.
+fn push_half_block(output: &mut String, top: Color, bottom: Color) {
+    let visible = |c: Color| c.a >= 128;
+
+    match (visible(top), visible(bottom)) {
+        (false, false) => output.push_str("\x1b[39;49m "),
+        (true, false) => {
+            let _ = write!(output, "\x1b[49;38;2;{};{};{}m▀", top.r, top.g, top.b);
+        }
+        (false, true) => {
+            let _ = write!(output, "\x1b[49;38;2;{};{};{}m▄", bottom.r, bottom.g, bottom.b);
+        }
+        (true, true) => {
+            let _ = write!(
+                output, "\x1b[38;2;{};{};{};48;2;{};{};{}m▀",
+                top.r, top.g, top.b, bottom.r, bottom.g, bottom.b
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATTERN: [&str; 4] = [
+        "#...",
+        "##..",
+        "..##",
+        "...#",
+    ];
+
+    /// Builds a bitmap with opaque white pixels at `#` and transparent ones elsewhere
+    fn bitmap(rows: &[&str]) -> Bitmap {
+        let mut bitmap = Bitmap::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, pixel) in row.chars().enumerate() {
+                if pixel == '#' {
+                    bitmap.data[y*bitmap.width + x] = Color::WHITE;
+                }
+            }
+        }
+        bitmap
+    }
+
+    fn blocks(rows: &[&str], mode: BlockMode) -> String {
+        bitmap(rows).to_blocks(&BlockOptions { mode, ..BlockOptions::default() })
+    }
+
+    #[test]
+    fn half_blocks_take_two_rows_per_line() {
+        assert_eq!(blocks(&PATTERN, BlockMode::HalfBlock), "█▄  \n  ▀█\n");
+        // The missing bottom row of an odd height counts as empty
+        assert_eq!(blocks(&PATTERN[..3], BlockMode::HalfBlock), "█▄  \n  ▀▀\n");
+    }
+
+    #[test]
+    fn quadrants_take_two_by_two_pixels() {
+        assert_eq!(blocks(&PATTERN, BlockMode::Quadrant), "▙ \n ▜\n");
+    }
+
+    #[test]
+    fn braille_takes_two_by_four_pixels() {
+        assert_eq!(blocks(&PATTERN, BlockMode::Braille), "⠓⢤\n");
+    }
+
+    #[test]
+    fn pixels_below_the_threshold_are_left_out() {
+        let mut faint = bitmap(&["##"]);
+        faint.data[1].a = 100;
+        let options = |threshold| BlockOptions { mode: BlockMode::Quadrant, dithering: Dithering::Threshold(threshold), color: false };
+
+        assert_eq!(faint.to_blocks(&options(128)), "▘\n");
+        assert_eq!(faint.to_blocks(&options(100)), "▀\n");
+    }
+}
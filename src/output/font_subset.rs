@@ -0,0 +1,225 @@
+use std::collections::BTreeSet;
+
+/// Tables needed to embed a TrueType font in a PDF (FontFile2). Everything else, including
+/// `cmap`, is dropped since glyphs are addressed by glyph id.
+const KEPT_TABLES: [&[u8; 4]; 9] = [b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp", b"prep"];
+
+// Composite glyph component flags
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+/// Subsets a TrueType font down to the given glyphs (plus `.notdef` and composite glyph
+/// components). Glyph ids are kept stable: unused glyphs remain in the font with empty outlines,
+/// so text can still be drawn with the original glyph ids.
+pub(crate) fn subset_font(font: &[u8], glyphs: &BTreeSet<u16>) -> Result<Vec<u8>, String> {
+    let tables = read_table_directory(font)?;
+    let table = |tag: &[u8; 4]| {
+        tables.iter()
+            .find(|(t, _)| t == tag)
+            .map(|(_, data)| *data)
+            .ok_or_else(|| format!("Font is missing the '{}' table", String::from_utf8_lossy(tag)))
+    };
+
+    let head = table(b"head")?;
+    let glyf = table(b"glyf")?;
+    let loca = table(b"loca")?;
+    let num_glyphs = read_u16(table(b"maxp")?, 4)? as usize;
+    let long_loca = read_u16(head, 50)? == 1;
+
+    let glyph_range = |gid: usize| -> Result<(usize, usize), String> {
+        let (start, end) = if long_loca {
+            (read_u32(loca, gid*4)? as usize, read_u32(loca, gid*4 + 4)? as usize)
+        } else {
+            (read_u16(loca, gid*2)? as usize * 2, read_u16(loca, gid*2 + 2)? as usize * 2)
+        };
+        if start > end || end > glyf.len() {
+            return Err(format!("Invalid loca entry for glyph {}", gid));
+        }
+        Ok((start, end))
+    };
+
+    // Collect the requested glyphs and, transitively, the components of composite glyphs
+    let mut kept = BTreeSet::new();
+    let mut pending: Vec<u16> = std::iter::once(0).chain(glyphs.iter().copied()).collect();
+    while let Some(gid) = pending.pop() {
+        if gid as usize >= num_glyphs || !kept.insert(gid) {
+            continue;
+        }
+        let (start, end) = glyph_range(gid as usize)?;
+        pending.extend(composite_components(&glyf[start..end])?);
+    }
+
+    // Rebuild glyf and a long format loca with only the kept outlines
+    let mut new_glyf = Vec::new();
+    let mut new_loca = Vec::with_capacity((num_glyphs + 1) * 4);
+    for gid in 0..num_glyphs {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        if kept.contains(&(gid as u16)) {
+            let (start, end) = glyph_range(gid)?;
+            new_glyf.extend_from_slice(&glyf[start..end]);
+            while new_glyf.len() % 4 != 0 {
+                new_glyf.push(0);
+            }
+        }
+    }
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+    let mut new_head = head.to_vec();
+    new_head[8..12].fill(0); // checkSumAdjustment, fixed up below
+    new_head[50..52].copy_from_slice(&1u16.to_be_bytes()); // indexToLocFormat = long
+
+    let mut out_tables: Vec<(&[u8; 4], Vec<u8>)> = Vec::new();
+    for tag in KEPT_TABLES {
+        let data = match tag {
+            b"glyf" => new_glyf.clone(),
+            b"loca" => new_loca.clone(),
+            b"head" => new_head.clone(),
+            // cvt, fpgm and prep are optional hinting tables
+            _ => match table(tag) {
+                Ok(data) => data.to_vec(),
+                Err(_) if matches!(tag, b"cvt " | b"fpgm" | b"prep") => continue,
+                Err(e) => return Err(e),
+            },
+        };
+        out_tables.push((tag, data));
+    }
+
+    Ok(write_font(&out_tables))
+}
+
+/// A table tag and its raw data
+type Table<'a> = ([u8; 4], &'a [u8]);
+
+fn read_table_directory(font: &[u8]) -> Result<Vec<Table<'_>>, String> {
+    let num_tables = read_u16(font, 4)? as usize;
+    let mut tables = Vec::with_capacity(num_tables);
+
+    for i in 0..num_tables {
+        let record = 12 + i*16;
+        let tag: [u8; 4] = font.get(record..record + 4)
+            .and_then(|t| t.try_into().ok())
+            .ok_or("Truncated table directory")?;
+        let offset = read_u32(font, record + 8)? as usize;
+        let length = read_u32(font, record + 12)? as usize;
+        let data = font.get(offset..offset + length).ok_or("Table extends past the end of the font")?;
+        tables.push((tag, data));
+    }
+
+    Ok(tables)
+}
+
+/// Glyph ids referenced by a composite glyph, or nothing for a simple glyph
+fn composite_components(glyph: &[u8]) -> Result<Vec<u16>, String> {
+    if glyph.is_empty() || (read_u16(glyph, 0)? as i16) >= 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut components = Vec::new();
+    let mut offset = 10;
+    loop {
+        let flags = read_u16(glyph, offset)?;
+        components.push(read_u16(glyph, offset + 2)?);
+        offset += 4;
+
+        offset += if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+        offset += if flags & WE_HAVE_A_SCALE != 0 {
+            2
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            4
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            8
+        } else {
+            0
+        };
+
+        if flags & MORE_COMPONENTS == 0 {
+            return Ok(components);
+        }
+    }
+}
+
+fn write_font(tables: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let num_tables = tables.len() as u16;
+    let entry_selector = 15 - num_tables.leading_zeros() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&0x00010000u32.to_be_bytes());
+    font.extend_from_slice(&num_tables.to_be_bytes());
+    font.extend_from_slice(&search_range.to_be_bytes());
+    font.extend_from_slice(&entry_selector.to_be_bytes());
+    font.extend_from_slice(&(num_tables * 16 - search_range).to_be_bytes());
+
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, data) in tables {
+        font.extend_from_slice(*tag);
+        font.extend_from_slice(&checksum(data).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+
+    let mut head_offset = 0;
+    for (tag, data) in tables {
+        if *tag == b"head" {
+            head_offset = font.len();
+        }
+        font.extend_from_slice(data);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+
+    let adjustment = 0xB1B0AFBAu32.wrapping_sub(checksum(&font));
+    font[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+
+    font
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "Unexpected end of font data".to_string())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "Unexpected end of font data".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use ttf_parser::{Face, GlyphId};
+
+    use super::*;
+    use crate::fonts::FONT_DATA;
+
+    #[test]
+    fn subsets_keep_glyph_ids_and_only_the_used_outlines() {
+        let original = Face::parse(FONT_DATA[0], 0).unwrap();
+        let glyph = |c| original.glyph_index(c).unwrap();
+        let used: BTreeSet<u16> = [glyph('x'), glyph('+')].iter().map(|gid| gid.0).collect();
+
+        let data = subset_font(FONT_DATA[0], &used).unwrap();
+        let subset = Face::parse(&data, 0).unwrap();
+
+        assert_eq!(subset.number_of_glyphs(), original.number_of_glyphs());
+        assert!(data.len() < FONT_DATA[0].len());
+        for gid in [glyph('x'), glyph('+')] {
+            assert_eq!(subset.glyph_bounding_box(gid), original.glyph_bounding_box(gid));
+        }
+        assert!(original.glyph_bounding_box(glyph('y')).is_some());
+        assert_eq!(subset.glyph_bounding_box(glyph('y')), None);
+        assert_eq!(subset.glyph_bounding_box(GlyphId(0)), original.glyph_bounding_box(GlyphId(0)));
+    }
+}
@@ -0,0 +1,95 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{bitmap::Bitmap, error::Error, output::png::PngOptions};
+
+#[derive(Debug, Clone)]
+pub struct ItermOptions {
+    /// Width in terminal cells, or the image's own width if `None`
+    pub columns: Option<u32>,
+    /// Height in terminal cells, or the image's own height if `None`
+    pub rows: Option<u32>,
+    /// Keeps the image's aspect ratio when both `columns` and `rows` are given
+    pub preserve_aspect_ratio: bool,
+}
+
+impl Default for ItermOptions {
+    fn default() -> Self {
+        Self {
+            columns: None,
+            rows: None,
+            preserve_aspect_ratio: true,
+        }
+    }
+}
+
+impl Bitmap {
+    /// Encodes the bitmap as a PNG inside an iTerm2 inline image escape sequence (`OSC 1337 File=`)
+    pub fn to_iterm(&self, options: &ItermOptions) -> Result<String, Error> {
+        let png = self.to_png(&PngOptions::default())?;
+
+        let size = |cells: Option<u32>| cells.map_or("auto".to_string(), |cells| cells.to_string());
+
+        Ok(format!(
+            "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio={}:{}\x07",
+            png.len(),
+            size(options.columns),
+            size(options.rows),
+            if options.preserve_aspect_ratio { 1 } else { 0 },
+            STANDARD.encode(png)
+        ))
+    }
+
+    /// Displays the bitmap in the terminal with the iTerm2 inline image protocol
+    pub fn print_iterm(&self, options: &ItermOptions) -> Result<(), Error> {
+        println!("{}", self.to_iterm(options)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmap::Color;
+
+    const HEADER: &str = "\x1b]1337;File=";
+
+    /// Splits an inline image sequence into its arguments and base64 payload
+    fn parts(output: &str) -> (&str, &str) {
+        let body = output.strip_prefix(HEADER).expect("starts with OSC 1337 File=");
+        let body = body.strip_suffix('\x07').expect("terminated by BEL");
+        body.split_once(':').expect("arguments and payload are separated by a colon")
+    }
+
+    #[test]
+    fn sequence_is_a_single_osc_1337_terminated_by_bel() {
+        let output = Bitmap::filled(3, 2, Color::WHITE).to_iterm(&ItermOptions::default()).unwrap();
+
+        assert!(output.starts_with(HEADER));
+        assert!(output.ends_with('\x07'));
+        assert_eq!(output.matches('\x1b').count(), 1);
+        assert_eq!(output.matches('\x07').count(), 1);
+    }
+
+    #[test]
+    fn payload_is_the_base64_png_and_size_matches_it() {
+        let bitmap = Bitmap::filled(3, 2, Color::rgba(10, 20, 30, 40));
+        let output = bitmap.to_iterm(&ItermOptions::default()).unwrap();
+        let (arguments, payload) = parts(&output);
+
+        let png = STANDARD.decode(payload).unwrap();
+        assert_eq!(png, bitmap.to_png(&PngOptions::default()).unwrap());
+        assert_eq!(
+            arguments,
+            format!("inline=1;size={};width=auto;height=auto;preserveAspectRatio=1", png.len())
+        );
+    }
+
+    #[test]
+    fn cell_size_and_stretching_are_passed_as_arguments() {
+        let options = ItermOptions { columns: Some(20), rows: Some(3), preserve_aspect_ratio: false };
+        let output = Bitmap::filled(3, 2, Color::WHITE).to_iterm(&options).unwrap();
+        let (arguments, _) = parts(&output);
+
+        assert!(arguments.ends_with(";width=20;height=3;preserveAspectRatio=0"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt::Write;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{bitmap::Bitmap, error::Error, output::png::PngOptions};
+
+/// Largest base64 payload allowed in one escape sequence by the kitty graphics protocol
+const CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KittyFormat {
+    /// Raw 32 bit RGBA pixels
+    Rgba,
+    /// A PNG file, which is usually much smaller than raw pixels
+    Png,
+}
+
+#[derive(Debug, Clone)]
+pub struct KittyOptions {
+    pub format: KittyFormat,
+    /// Lets the image be placed again or deleted later without retransmitting it
+    pub image_id: Option<u32>,
+    /// Number of cell columns the image is scaled to fill
+    pub columns: Option<u32>,
+    /// Number of cell rows the image is scaled to fill
+    pub rows: Option<u32>,
+    /// Asks the terminal not to send back responses, which would otherwise appear on stdin
+    pub quiet: bool,
+}
+
+impl Default for KittyOptions {
+    fn default() -> Self {
+        Self {
+            format: KittyFormat::Png,
+            image_id: None,
+            columns: None,
+            rows: None,
+            quiet: true,
+        }
+    }
+}
+
+impl Bitmap {
+    /// Encodes the bitmap as kitty graphics protocol escape sequences that transmit
+    /// the image and display it at the cursor
+    pub fn to_kitty(&self, options: &KittyOptions) -> Result<String, Error> {
+        let (payload, format_keys) = match options.format {
+            KittyFormat::Rgba => {
+                let pixels: Vec<u8> = self.data.iter().flat_map(|p| [p.r, p.g, p.b, p.a]).collect();
+                (pixels, format!("f=32,s={},v={}", self.width, self.height))
+            }
+            KittyFormat::Png => (self.to_png(&PngOptions::default())?, "f=100".to_string()),
+        };
+
+        let mut keys = format!("a=T,{}", format_keys);
+        append_placement_keys(&mut keys, options.image_id, options.columns, options.rows, options.quiet);
+
+        let encoded = STANDARD.encode(payload);
+        let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(CHUNK_SIZE).collect();
+
+        let mut output = String::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let more = if i + 1 < chunks.len() { 1 } else { 0 };
+            // Only the first chunk carries the image keys, the rest just continue the payload
+            let chunk_keys = if i == 0 { format!("{},m={}", keys, more) } else { format!("m={}", more) };
+            // Base64 output is always ASCII
+            let _ = write!(output, "\x1b_G{};{}\x1b\\", chunk_keys, std::str::from_utf8(chunk).unwrap());
+        }
+
+        Ok(output)
+    }
+
+    /// Transmits the bitmap with the kitty graphics protocol and displays it in the terminal
+    pub fn print_kitty(&self, options: &KittyOptions) -> Result<(), Error> {
+        println!("{}", self.to_kitty(options)?);
+        Ok(())
+    }
+}
+
+/// Displays a previously transmitted image again at the cursor
+pub fn kitty_place(image_id: u32, columns: Option<u32>, rows: Option<u32>, quiet: bool) -> String {
+    let mut keys = "a=p".to_string();
+    append_placement_keys(&mut keys, Some(image_id), columns, rows, quiet);
+    format!("\x1b_G{}\x1b\\", keys)
+}
+
+/// Deletes an image and all of its placements, freeing its data in the terminal
+pub fn kitty_delete(image_id: u32, quiet: bool) -> String {
+    let mut keys = "a=d,d=I".to_string();
+    append_placement_keys(&mut keys, Some(image_id), None, None, quiet);
+    format!("\x1b_G{}\x1b\\", keys)
+}
+
+fn append_placement_keys(keys: &mut String, image_id: Option<u32>, columns: Option<u32>, rows: Option<u32>, quiet: bool) {
+    if let Some(image_id) = image_id {
+        let _ = write!(keys, ",i={}", image_id);
+    }
+    if let Some(columns) = columns {
+        let _ = write!(keys, ",c={}", columns);
+    }
+    if let Some(rows) = rows {
+        let _ = write!(keys, ",r={}", rows);
+    }
+    if quiet {
+        keys.push_str(",q=2");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmap::Color;
+
+    /// Splits the output into the control keys and payload of each escape sequence
+    fn sequences(output: &str) -> Vec<(&str, &str)> {
+        output.split_terminator("\x1b\\")
+            .map(|sequence| sequence.strip_prefix("\x1b_G").expect("every sequence starts with APC G"))
+            .map(|sequence| sequence.split_once(';').unwrap_or((sequence, "")))
+            .collect()
+    }
+
+    fn rgba_options() -> KittyOptions {
+        KittyOptions { format: KittyFormat::Rgba, ..KittyOptions::default() }
+    }
+
+    #[test]
+    fn small_image_is_sent_in_one_final_chunk() {
+        let output = Bitmap::filled(2, 1, Color::WHITE).to_kitty(&rgba_options()).unwrap();
+
+        assert_eq!(sequences(&output), vec![("a=T,f=32,s=2,v=1,q=2,m=0", "//////////8=")]);
+    }
+
+    #[test]
+    fn large_payload_is_split_into_4096_byte_chunks() {
+        // 40x40 RGBA pixels are 6400 bytes, 8536 bytes of base64
+        let bitmap = Bitmap::filled(40, 40, Color::rgba(1, 2, 3, 4));
+        let output = bitmap.to_kitty(&rgba_options()).unwrap();
+        let sequences = sequences(&output);
+
+        assert_eq!(sequences.len(), 3);
+        assert_eq!(sequences.iter().map(|(_, payload)| payload.len()).collect::<Vec<_>>(), vec![4096, 4096, 344]);
+
+        let payload: String = sequences.iter().map(|(_, payload)| *payload).collect();
+        assert_eq!(STANDARD.decode(payload).unwrap(), [1, 2, 3, 4].repeat(40 * 40));
+    }
+
+    #[test]
+    fn only_the_first_chunk_carries_image_keys() {
+        let options = KittyOptions { image_id: Some(7), columns: Some(10), ..rgba_options() };
+        let output = Bitmap::filled(40, 40, Color::WHITE).to_kitty(&options).unwrap();
+        let keys: Vec<&str> = sequences(&output).iter().map(|(keys, _)| *keys).collect();
+
+        assert_eq!(keys, vec!["a=T,f=32,s=40,v=40,i=7,c=10,q=2,m=1", "m=1", "m=0"]);
+    }
+
+    #[test]
+    fn place_and_delete_refer_to_the_image_id() {
+        assert_eq!(kitty_place(7, Some(10), Some(2), true), "\x1b_Ga=p,i=7,c=10,r=2,q=2\x1b\\");
+        assert_eq!(kitty_delete(7, false), "\x1b_Ga=d,d=I,i=7\x1b\\");
+    }
+}
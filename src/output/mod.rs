@@ -0,0 +1,15 @@
+pub(crate) mod blocks;
+mod font_subset;
+pub(crate) mod iterm;
+pub(crate) mod kitty;
+pub(crate) mod pdf;
+pub(crate) mod png;
+mod svg;
+pub(crate) mod vector;
+
+/// Formats a coordinate with at most 3 decimals and no trailing zeros
+pub(crate) fn fmt_num(value: f32) -> String {
+    let formatted = format!("{:.3}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" { "0".to_string() } else { trimmed.to_string() }
+}
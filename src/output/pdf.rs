@@ -0,0 +1,326 @@
+use std::{collections::{BTreeMap, BTreeSet}, fmt::Write as _, io::Write as _};
+
+use flate2::{write::ZlibEncoder, Compression};
+use ttf_parser::{name_id, GlyphId};
+
+use crate::{bitmap::Color, fonts::{FACES, FONT_DATA}, output::{fmt_num, font_subset::subset_font, vector::{VectorCanvas, VectorItem}}};
+
+#[derive(Debug, Clone)]
+pub struct PdfOptions {
+    /// Size of one em in points
+    pub font_size: f32,
+    /// Blank space around each formula in points
+    pub margin: f32,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            font_size: 12.,
+            margin: 0.,
+        }
+    }
+}
+
+struct PdfPage {
+    canvas: VectorCanvas,
+    /// Points per canvas pixel
+    scale: f32,
+    background: Color,
+}
+
+/// Builds a PDF with one tightly cropped page per formula.
+///
+/// Glyphs are drawn with the embedded KaTeX fonts, subset to the glyphs used across the whole
+/// document, and rules are drawn as filled rectangles. Alpha is not preserved.
+pub(crate) struct PdfWriter {
+    options: PdfOptions,
+    pages: Vec<PdfPage>,
+}
+
+impl PdfWriter {
+    pub fn new(options: PdfOptions) -> Self {
+        Self {
+            options,
+            pages: Vec::new(),
+        }
+    }
+
+    /// Adds a page for a formula that was laid out with an em size of `em_size` pixels
+    pub fn add_page(&mut self, canvas: VectorCanvas, em_size: f32, background: Color) {
+        let scale = self.options.font_size / em_size;
+        self.pages.push(PdfPage { canvas, scale, background });
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        let mut objects: Vec<Vec<u8>> = Vec::new();
+        let mut reserve = || {
+            objects.push(Vec::new());
+            objects.len()
+        };
+
+        let catalog_id = reserve();
+        let pages_id = reserve();
+
+        // Glyphs used per font across the whole document
+        let mut used_glyphs: BTreeMap<usize, BTreeMap<u16, char>> = BTreeMap::new();
+        for page in &self.pages {
+            for item in &page.canvas.items {
+                if let VectorItem::Glyph { font_index, glyph_index, character, .. } = *item {
+                    used_glyphs.entry(font_index).or_default().insert(glyph_index, character);
+                }
+            }
+        }
+
+        let mut font_ids = BTreeMap::new();
+        for font_index in used_glyphs.keys() {
+            font_ids.insert(*font_index, reserve());
+        }
+
+        let page_ids: Vec<(usize, usize)> = self.pages.iter().map(|_| (reserve(), reserve())).collect();
+
+        objects[catalog_id - 1] = format!("<< /Type /Catalog /Pages {} 0 R >>", pages_id).into_bytes();
+        objects[pages_id - 1] = format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            page_ids.iter().map(|(id, _)| format!("{} 0 R", id)).collect::<Vec<_>>().join(" "),
+            page_ids.len()
+        ).into_bytes();
+
+        for (font_index, glyphs) in &used_glyphs {
+            let font_id = font_ids[font_index];
+            let font_objects = font_objects(*font_index, glyphs, objects.len() + 1)?;
+            objects[font_id - 1] = font_objects[0].clone();
+            objects.extend(font_objects.into_iter().skip(1));
+        }
+
+        let font_resources: String = font_ids.iter()
+            .map(|(font_index, id)| format!("/F{} {} 0 R ", font_index, id))
+            .collect();
+
+        for (page, (page_id, content_id)) in self.pages.iter().zip(&page_ids) {
+            let margin = self.options.margin;
+            let width = page.canvas.width as f32 * page.scale + 2. * margin;
+            let height = page.canvas.height as f32 * page.scale + 2. * margin;
+
+            objects[*page_id - 1] = format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources << /Font << {}>> >> /Contents {} 0 R >>",
+                pages_id, fmt_num(width), fmt_num(height), font_resources, content_id
+            ).into_bytes();
+            objects[*content_id - 1] = stream_object("", content_stream(page, margin, height).as_bytes())?;
+        }
+
+        let mut pdf = b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            pdf.extend_from_slice(object);
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref_offset = pdf.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            trailer,
+            "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1, catalog_id, xref_offset
+        );
+        pdf.extend_from_slice(trailer.as_bytes());
+
+        Ok(pdf)
+    }
+}
+
+fn content_stream(page: &PdfPage, margin: f32, page_height: f32) -> String {
+    let mut content = String::new();
+    let x_pos = |x: f32| fmt_num(margin + x * page.scale);
+    let y_pos = |y: f32| fmt_num(page_height - margin - y * page.scale);
+
+    if page.background.a > 0 {
+        let _ = writeln!(
+            content, "{} 0 0 {} {} re f",
+            fill_color(page.background), fmt_num(page.canvas.width as f32 * page.scale + 2. * margin), fmt_num(page_height)
+        );
+    }
+
+    for item in &page.canvas.items {
+        match *item {
+            VectorItem::Glyph { font_index, glyph_index, x, y, px, color, .. } => {
+                let _ = writeln!(
+                    content, "BT {} /F{} {} Tf 1 0 0 1 {} {} Tm <{:04X}> Tj ET",
+                    fill_color(color), font_index, fmt_num(px * page.scale), x_pos(x), y_pos(y), glyph_index
+                );
+            }
+            VectorItem::Rect { x, y, width, height, color } => {
+                let _ = writeln!(
+                    content, "{} {} {} {} {} re f",
+                    fill_color(color), x_pos(x), y_pos(y + height), fmt_num(width * page.scale), fmt_num(height * page.scale)
+                );
+            }
+        }
+    }
+
+    content
+}
+
+fn fill_color(color: Color) -> String {
+    format!(
+        "{} {} {} rg",
+        fmt_num(color.r as f32 / 255.), fmt_num(color.g as f32 / 255.), fmt_num(color.b as f32 / 255.)
+    )
+}
+
+/// Builds the Type0 font, CID font, descriptor, font file and ToUnicode objects for one font.
+/// The Type0 font comes first; the others are numbered consecutively from `next_id`.
+fn font_objects(font_index: usize, glyphs: &BTreeMap<u16, char>, next_id: usize) -> Result<Vec<Vec<u8>>, String> {
+    let face = &FACES[font_index];
+    let (cid_font_id, descriptor_id, font_file_id, to_unicode_id) = (next_id, next_id + 1, next_id + 2, next_id + 3);
+
+    let to_pdf_units = |v: f32| fmt_num(v * 1000. / face.units_per_em() as f32);
+
+    let glyph_set: BTreeSet<u16> = glyphs.keys().copied().collect();
+    let base_font = format!("{}+{}", subset_tag(&glyph_set), postscript_name(font_index));
+
+    let widths: String = glyphs.keys()
+        .map(|gid| format!("{} [{}]", gid, to_pdf_units(face.glyph_hor_advance(GlyphId(*gid)).unwrap_or(0) as f32)))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let bbox = face.global_bounding_box();
+    let flags = if face.is_italic() { 4 | 64 } else { 4 }; // Symbolic (+ Italic)
+
+    let type0 = format!(
+        "<< /Type /Font /Subtype /Type0 /BaseFont /{} /Encoding /Identity-H /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>",
+        base_font, cid_font_id, to_unicode_id
+    );
+    let cid_font = format!(
+        "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /{} /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> /FontDescriptor {} 0 R /W [{}] /CIDToGIDMap /Identity >>",
+        base_font, descriptor_id, widths
+    );
+    let descriptor = format!(
+        "<< /Type /FontDescriptor /FontName /{} /Flags {} /FontBBox [{} {} {} {}] /ItalicAngle {} /Ascent {} /Descent {} /CapHeight {} /StemV 80 /FontFile2 {} 0 R >>",
+        base_font, flags,
+        to_pdf_units(bbox.x_min as f32), to_pdf_units(bbox.y_min as f32), to_pdf_units(bbox.x_max as f32), to_pdf_units(bbox.y_max as f32),
+        fmt_num(face.italic_angle().unwrap_or(0.)),
+        to_pdf_units(face.ascender() as f32), to_pdf_units(face.descender() as f32),
+        to_pdf_units(face.capital_height().unwrap_or(face.ascender()) as f32),
+        font_file_id
+    );
+
+    let font_file = subset_font(FONT_DATA[font_index], &glyph_set)?;
+
+    Ok(vec![
+        type0.into_bytes(),
+        cid_font.into_bytes(),
+        descriptor.into_bytes(),
+        stream_object(&format!("/Length1 {} ", font_file.len()), &font_file)?,
+        stream_object("", to_unicode_cmap(glyphs).as_bytes())?,
+    ])
+}
+
+/// CMap that maps glyph ids back to characters, so text can be searched and copied
+fn to_unicode_cmap(glyphs: &BTreeMap<u16, char>) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n"
+    );
+
+    let entries: Vec<(&u16, &char)> = glyphs.iter().collect();
+    // A bfchar block may hold at most 100 entries
+    for chunk in entries.chunks(100) {
+        let _ = writeln!(cmap, "{} beginbfchar", chunk.len());
+        for (gid, character) in chunk {
+            let utf16: String = character.encode_utf16(&mut [0; 2]).iter().map(|u| format!("{:04X}", u)).collect();
+            let _ = writeln!(cmap, "<{:04X}> <{}>", gid, utf16);
+        }
+        cmap.push_str("endbfchar\n");
+    }
+
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap
+}
+
+fn stream_object(extra_entries: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).map_err(|e| e.to_string())?;
+    let compressed = encoder.finish().map_err(|e| e.to_string())?;
+
+    let mut object = format!("<< /Length {} {}/Filter /FlateDecode >>\nstream\n", compressed.len(), extra_entries).into_bytes();
+    object.extend_from_slice(&compressed);
+    object.extend_from_slice(b"\nendstream");
+    Ok(object)
+}
+
+fn postscript_name(font_index: usize) -> String {
+    FACES[font_index].names()
+        .into_iter()
+        .find(|name| name.name_id == name_id::POST_SCRIPT_NAME)
+        .and_then(|name| name.to_string())
+        .unwrap_or_else(|| format!("RusTeXFont{}", font_index))
+}
+
+/// Six uppercase letters identifying the subset, as required for subset font names
+fn subset_tag(glyphs: &BTreeSet<u16>) -> String {
+    let mut hash = glyphs.iter().fold(0xcbf29ce484222325u64, |hash, gid| {
+        (hash ^ *gid as u64).wrapping_mul(0x100000001b3)
+    });
+
+    (0..6).map(|_| {
+        let letter = (b'A' + (hash % 26) as u8) as char;
+        hash /= 26;
+        letter
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{element::KElement, renderer::RusTeX, settings::TeXSettings};
+
+    use super::*;
+
+    fn render(sources: &[&str]) -> Vec<u8> {
+        let elements: Vec<KElement> = sources.iter().map(|source| KElement::parse(source).unwrap()).collect();
+        RusTeX::new(TeXSettings::default()).render_pdf(&elements, PdfOptions::default()).unwrap()
+    }
+
+    /// Byte offsets listed in the cross-reference table, in object number order
+    fn xref_offsets(pdf: &[u8]) -> Vec<usize> {
+        // Everything after the last stream is ASCII
+        let startxref = pdf.windows(10).rposition(|window| window == b"startxref\n").unwrap();
+        let start: usize = std::str::from_utf8(&pdf[startxref + 10..]).unwrap().lines().next().unwrap().parse().unwrap();
+        assert!(pdf[start..].starts_with(b"xref\n"));
+
+        let mut lines = std::str::from_utf8(&pdf[start..]).unwrap().lines().skip(1);
+        let count: usize = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+        lines.skip(1).take(count - 1)
+            .map(|entry| entry[..10].parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn xref_offsets_point_at_their_objects() {
+        let pdf = render(&[r"\frac{x}{2}\pm y"]);
+        let offsets = xref_offsets(&pdf);
+
+        assert!(offsets.len() > 5);
+        for (i, offset) in offsets.into_iter().enumerate() {
+            let header = format!("{} 0 obj\n", i + 1);
+            assert!(pdf[offset..].starts_with(header.as_bytes()), "object {} is not at {}", i + 1, offset);
+        }
+    }
+
+    #[test]
+    fn every_formula_gets_a_page() {
+        let pdf = String::from_utf8_lossy(&render(&["x", "y^2", r"\frac{1}{2}"])).into_owned();
+
+        assert!(pdf.contains("/Count 3 >>"));
+        assert_eq!(pdf.matches("/Type /Page ").count(), 3);
+        // Both fonts are embedded once for the whole document
+        assert_eq!(pdf.matches("/Subtype /Type0").count(), 2);
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use png::{BitDepth, ColorType, Encoder, PixelDimensions, Unit};
+
+use crate::{bitmap::Bitmap, error::Error};
+
+/// Keyword of the tEXt chunk holding the LaTeX source of the formula
+pub const SOURCE_KEYWORD: &str = "LaTeX";
+
+const METERS_PER_INCH: f32 = 0.0254;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngColorType {
+    GrayscaleAlpha,
+    Rgba,
+}
+
+#[derive(Debug, Clone)]
+pub struct PngOptions {
+    pub color_type: PngColorType,
+    /// Written to the pHYs chunk so viewers can show the image at its intended physical size
+    pub dpi: Option<f32>,
+    /// LaTeX source of the formula, saved in a tEXt chunk (iTXt if it is not Latin-1)
+    pub source: Option<String>,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self {
+            color_type: PngColorType::Rgba,
+            dpi: None,
+            source: None,
+        }
+    }
+}
+
+impl Bitmap {
+    /// Encodes the bitmap as a PNG file
+    pub fn to_png(&self, options: &PngOptions) -> Result<Vec<u8>, Error> {
+        let mut buffer = Vec::new();
+
+        let mut encoder = Encoder::new(&mut buffer, self.width as u32, self.height as u32);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.set_color(match options.color_type {
+            PngColorType::GrayscaleAlpha => ColorType::GrayscaleAlpha,
+            PngColorType::Rgba => ColorType::Rgba,
+        });
+
+        if let Some(dpi) = options.dpi {
+            let ppm = (dpi / METERS_PER_INCH).round() as u32;
+            encoder.set_pixel_dims(Some(PixelDimensions { xppu: ppm, yppu: ppm, unit: Unit::Meter }));
+        }
+
+        if let Some(source) = &options.source {
+            if source.chars().all(|c| (c as u32) < 256) {
+                encoder.add_text_chunk(SOURCE_KEYWORD.to_string(), source.clone())
+            } else {
+                encoder.add_itxt_chunk(SOURCE_KEYWORD.to_string(), source.clone())
+            }.map_err(png_error)?;
+        }
+
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer.write_image_data(&self.png_pixel_data(options.color_type)).map_err(png_error)?;
+        writer.finish().map_err(png_error)?;
+
+        Ok(buffer)
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>, options: &PngOptions) -> Result<(), Error> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_png(options)?).map_err(|e| Error::Output(format!("{}: {}", path.display(), e)))
+    }
+
+    fn png_pixel_data(&self, color_type: PngColorType) -> Vec<u8> {
+        match color_type {
+            PngColorType::GrayscaleAlpha => self.data.iter()
+                .flat_map(|p| {
+                    let luma = 0.299 * p.r as f32 + 0.587 * p.g as f32 + 0.114 * p.b as f32;
+                    [luma.round() as u8, p.a]
+                })
+                .collect(),
+            PngColorType::Rgba => self.data.iter()
+                .flat_map(|p| [p.r, p.g, p.b, p.a])
+                .collect(),
+        }
+    }
+}
+
+fn png_error(error: png::EncodingError) -> Error {
+    Error::Output(format!("PNG encoding failed: {}", error))
+}
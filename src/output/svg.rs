@@ -0,0 +1,110 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use ttf_parser::{GlyphId, OutlineBuilder};
+
+use crate::{bitmap::Color, fonts::FACES, output::{fmt_num, vector::{VectorCanvas, VectorItem}}};
+
+impl VectorCanvas {
+    /// Serializes the canvas as a standalone SVG document.
+    ///
+    /// The viewBox is shifted so that y = 0 is the baseline of the formula, and
+    /// `vertical-align` is set so the image lines up with surrounding text when inlined.
+    pub fn to_svg(&self, background: Color) -> String {
+        let baseline = self.baseline.unwrap_or(self.height as f32);
+        let depth = self.height as f32 - baseline;
+
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="{}" viewBox="0 {} {} {}" style="vertical-align: {}px">"#,
+            self.width, self.height, fmt_num(-baseline), self.width, self.height, fmt_num(-depth)
+        );
+
+        // Every distinct glyph is emitted once in font units and then referenced
+        let mut glyph_ids = BTreeMap::new();
+        for item in &self.items {
+            if let VectorItem::Glyph { font_index, glyph_index, .. } = *item {
+                glyph_ids.entry((font_index, glyph_index)).or_insert_with(|| glyph_path(font_index, glyph_index));
+            }
+        }
+
+        svg.push_str("<defs>");
+        for ((font_index, glyph_index), path) in &glyph_ids {
+            let _ = write!(svg, r#"<path id="g{}-{}" d="{}"/>"#, font_index, glyph_index, path);
+        }
+        svg.push_str("</defs>");
+
+        if background.a > 0 {
+            let _ = write!(
+                svg,
+                r#"<rect x="0" y="{}" width="{}" height="{}"{}/>"#,
+                fmt_num(-baseline), self.width, self.height, fill(background)
+            );
+        }
+
+        for item in &self.items {
+            match *item {
+                VectorItem::Glyph { font_index, glyph_index, x, y, px, color, .. } => {
+                    let scale = px / FACES[font_index].units_per_em() as f32;
+                    let _ = write!(
+                        svg,
+                        r##"<use xlink:href="#g{}-{}" transform="translate({} {}) scale({} {})"{}/>"##,
+                        font_index, glyph_index, fmt_num(x), fmt_num(y - baseline), fmt_num(scale), fmt_num(-scale), fill(color)
+                    );
+                }
+                VectorItem::Rect { x, y, width, height, color } => {
+                    let _ = write!(
+                        svg,
+                        r#"<rect x="{}" y="{}" width="{}" height="{}"{}/>"#,
+                        fmt_num(x), fmt_num(y - baseline), fmt_num(width), fmt_num(height), fill(color)
+                    );
+                }
+            }
+        }
+
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+/// Builds SVG path data for a glyph outline, in font units with y pointing up
+struct PathBuilder(String);
+
+impl OutlineBuilder for PathBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let _ = write!(self.0, "M{} {}", fmt_num(x), fmt_num(y));
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let _ = write!(self.0, "L{} {}", fmt_num(x), fmt_num(y));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let _ = write!(self.0, "Q{} {} {} {}", fmt_num(x1), fmt_num(y1), fmt_num(x), fmt_num(y));
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let _ = write!(
+            self.0, "C{} {} {} {} {} {}",
+            fmt_num(x1), fmt_num(y1), fmt_num(x2), fmt_num(y2), fmt_num(x), fmt_num(y)
+        );
+    }
+
+    fn close(&mut self) {
+        self.0.push('Z');
+    }
+}
+
+fn glyph_path(font_index: usize, glyph_index: u16) -> String {
+    let mut builder = PathBuilder(String::new());
+    FACES[font_index].outline_glyph(GlyphId(glyph_index), &mut builder);
+    builder.0
+}
+
+fn fill(color: Color) -> String {
+    let mut attrs = format!(r##" fill="#{:02x}{:02x}{:02x}""##, color.r, color.g, color.b);
+    if color.a < 255 {
+        let _ = write!(attrs, r#" fill-opacity="{}""#, fmt_num(color.a as f32 / 255.));
+    }
+    attrs
+}
@@ -0,0 +1,76 @@
+use fontdue::layout::GlyphPosition;
+
+use crate::{bitmap::Color, canvas::Canvas, fonts::FONTS, glyph_cache::GlyphCache};
+
+pub(crate) enum VectorItem {
+    Glyph {
+        font_index: usize,
+        glyph_index: u16,
+        /// Character the glyph was shaped from
+        character: char,
+        /// Pen position on the baseline
+        x: f32,
+        y: f32,
+        px: f32,
+        color: Color,
+    },
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: Color,
+    },
+}
+
+/// A canvas that records glyphs and rules as vector shapes instead of pixels.
+/// Serialized by the SVG and PDF backends.
+pub(crate) struct VectorCanvas {
+    pub width: usize,
+    pub height: usize,
+    pub baseline: Option<f32>,
+    pub items: Vec<VectorItem>,
+}
+
+impl Canvas for VectorCanvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            baseline: None,
+            items: Vec::new(),
+        }
+    }
+
+    fn draw_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, thickness: f32, color: Color) {
+        // Formulas only ever contain horizontal and vertical rules
+        let (x, width) = (x0.min(x1), (x1 - x0).abs());
+        let (y, height) = (y0.min(y1), (y1 - y0).abs());
+
+        self.items.push(VectorItem::Rect {
+            x: if width == 0. { x - thickness / 2. } else { x },
+            y: if height == 0. { y - thickness / 2. } else { y },
+            width: width.max(thickness),
+            height: height.max(thickness),
+            color,
+        });
+    }
+
+    fn draw_glyph(&mut self, glyph: &GlyphPosition, x: f32, y: f32, baseline: f32, color: Color, _cache: &GlyphCache) {
+        let metrics = FONTS[glyph.font_index].metrics_indexed(glyph.key.glyph_index, glyph.key.px);
+
+        self.items.push(VectorItem::Glyph {
+            font_index: glyph.font_index,
+            glyph_index: glyph.key.glyph_index,
+            character: glyph.parent,
+            x: x + glyph.x - metrics.bounds.xmin,
+            y: y + baseline,
+            px: glyph.key.px,
+            color,
+        });
+    }
+
+    fn set_baseline(&mut self, baseline: f32) {
+        self.baseline = Some(baseline);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{bitmap::Bitmap, element::KElement, error::Error, glyph_cache::{GlyphCache, GlyphCacheStats}, layout::LayoutBox, output::{pdf::{PdfOptions, PdfWriter}, vector::VectorCanvas}, settings::TeXSettings};
+
+/// Largest canvas a formula may be drawn on, in pixels
+const MAX_PIXELS: f32 = (1 << 28) as f32;
+
+/// Lays out and draws formulas. Keep one around to reuse its glyph cache across renders.
+///
+/// A renderer is `Send + Sync`: share it between threads (in an `Arc` or by reference) and render
+/// different formulas concurrently. Each render keeps its scratch state to itself.
+pub struct RusTeX {
+    pub(crate) settings: TeXSettings,
+    /// Rasterized glyphs shared by every render on this instance
+    pub(crate) glyph_cache: GlyphCache,
+}
+
+impl RusTeX {
+    pub fn new(settings: TeXSettings) -> Self {
+        Self {
+            glyph_cache: GlyphCache::new(settings.glyph_cache_capacity),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &TeXSettings {
+        &self.settings
+    }
+
+    /// Measures and positions the formula without drawing it. Fails when the formula is too
+    /// large to draw.
+    pub fn layout(&self, root_element: &KElement) -> Result<LayoutBox, Error> {
+        let layout = root_element.layout_root(&self.settings);
+        let (width, height) = (layout.width().ceil(), layout.total_height().ceil());
+
+        if !width.is_finite() || !height.is_finite() || width*height > MAX_PIXELS {
+            return Err(Error::Render(format!("The formula is too large to draw at {}x{} pixels", width, height)));
+        }
+
+        Ok(layout)
+    }
+
+    pub fn rasterize(&self, root_element: &KElement) -> Result<Bitmap, Error> {
+        let mut bitmap: Bitmap = self.layout(root_element)?.render(&self.glyph_cache, self.settings.blend_mode);
+        bitmap.fill_background(self.settings.background);
+        Ok(bitmap)
+    }
+
+    /// Renders the formula as an SVG document with glyph outlines instead of pixels
+    pub fn render_svg(&self, root_element: &KElement) -> Result<String, Error> {
+        let canvas: VectorCanvas = self.layout(root_element)?.render(&self.glyph_cache, self.settings.blend_mode);
+        Ok(canvas.to_svg(self.settings.background))
+    }
+
+    /// Renders each formula onto its own tightly cropped page of a PDF document
+    pub fn render_pdf(&self, root_elements: &[KElement], options: PdfOptions) -> Result<Vec<u8>, Error> {
+        let scale = self.settings.scale;
+        let mut writer = PdfWriter::new(options);
+
+        for root_element in root_elements {
+            let canvas: VectorCanvas = self.layout(root_element)?.render(&self.glyph_cache, self.settings.blend_mode);
+            writer.add_page(canvas, scale, self.settings.background);
+        }
+
+        writer.finish().map_err(Error::Output)
+    }
+
+    pub fn glyph_cache_stats(&self) -> GlyphCacheStats {
+        self.glyph_cache.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formulas_too_large_to_draw_are_an_error() {
+        let element = KElement::parse(r"\frac{x}{y}").unwrap();
+        let huge = RusTeX::new(TeXSettings::builder().scale(1e6).build().unwrap());
+        assert!(matches!(huge.rasterize(&element), Err(Error::Render(_))));
+        assert!(matches!(huge.render_svg(&element), Err(Error::Render(_))));
+
+        let bitmap = RusTeX::new(TeXSettings::default()).rasterize(&element).unwrap();
+        assert!(bitmap.width > 0 && bitmap.height > 0);
+    }
+
+    #[test]
+    fn lenient_formulas_lay_out_around_their_errors() {
+        let renderer = RusTeX::new(TeXSettings::default());
+        let (element, errors) = KElement::parse_lenient(r"\fract{1}{2}+x");
+        assert_eq!(errors.len(), 1);
+
+        let with_error = renderer.layout(&element).unwrap();
+        let valid_part = renderer.layout(&KElement::parse("+x").unwrap()).unwrap();
+        assert!(with_error.width() > valid_part.width());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{bitmap::{BlendMode, Color}, error::Error};
+
+/// How formulas are sized and colored. Create it with [`TeXSettings::builder`] or use the defaults.
+#[derive(Debug, Clone)]
+pub struct TeXSettings {
+    pub(crate) scale: f32,
+    /// Lay formulas out in display style, otherwise in the smaller text style used inline
+    pub(crate) display: bool,
+    /// Color of glyphs and rules
+    pub(crate) foreground: Color,
+    /// Color the finished formula is composited on. Use `Color::TRANSPARENT` for a transparent background.
+    pub(crate) background: Color,
+    /// Color of source text that failed to parse in lenient mode
+    pub(crate) error_color: Color,
+    /// Number of rasterized glyphs kept between renders, 0 disables the cache
+    pub(crate) glyph_cache_capacity: usize,
+    /// How overlapping glyphs and rules are combined in bitmap output
+    pub(crate) blend_mode: BlendMode,
+}
+
+impl Default for TeXSettings {
+    fn default() -> Self {
+        Self {
+            scale: 100.,
+            display: true,
+            foreground: Color::WHITE,
+            background: Color::BLACK,
+            error_color: Color::rgb(0xcc, 0x00, 0x00),
+            glyph_cache_capacity: 1024,
+            blend_mode: BlendMode::SourceOver,
+        }
+    }
+}
+
+impl TeXSettings {
+    /// Starts from the default settings
+    pub fn builder() -> TeXSettingsBuilder {
+        TeXSettingsBuilder::default()
+    }
+
+    /// Font size in pixels
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn display(&self) -> bool {
+        self.display
+    }
+
+    pub fn foreground(&self) -> Color {
+        self.foreground
+    }
+
+    pub fn background(&self) -> Color {
+        self.background
+    }
+
+    pub fn error_color(&self) -> Color {
+        self.error_color
+    }
+
+    pub fn glyph_cache_capacity(&self) -> usize {
+        self.glyph_cache_capacity
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+}
+
+/// Builds `TeXSettings`, checking the values once they are all set
+#[derive(Debug, Clone, Default)]
+pub struct TeXSettingsBuilder {
+    settings: TeXSettings,
+}
+
+impl TeXSettingsBuilder {
+    /// Font size in pixels, 100 by default
+    pub fn scale(&mut self, scale: f32) -> &mut Self {
+        self.settings.scale = scale;
+        self
+    }
+
+    /// Whether formulas are laid out in display style (the default) or in the text style used inline
+    pub fn display(&mut self, display: bool) -> &mut Self {
+        self.settings.display = display;
+        self
+    }
+
+    pub fn foreground(&mut self, color: Color) -> &mut Self {
+        self.settings.foreground = color;
+        self
+    }
+
+    pub fn background(&mut self, color: Color) -> &mut Self {
+        self.settings.background = color;
+        self
+    }
+
+    pub fn error_color(&mut self, color: Color) -> &mut Self {
+        self.settings.error_color = color;
+        self
+    }
+
+    pub fn glyph_cache_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.settings.glyph_cache_capacity = capacity;
+        self
+    }
+
+    pub fn blend_mode(&mut self, mode: BlendMode) -> &mut Self {
+        self.settings.blend_mode = mode;
+        self
+    }
+
+    pub fn build(&self) -> Result<TeXSettings, Error> {
+        let scale = self.settings.scale;
+        if !scale.is_finite() || scale <= 0. {
+            return Err(Error::InvalidSettings(format!("Scale must be a positive number, got {}", scale)));
+        }
+
+        Ok(self.settings.clone())
+    }
+}